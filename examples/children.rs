@@ -12,7 +12,7 @@ use serde_json::{from_value, Value};
 use stdweb::PromiseFuture;
 use stdweb::web::{document, set_timeout, INonElementParentNode};
 use virtual_view::{Children, Component, EventManager, Instance, Props, Renderer, Updater, View};
-use virtual_view_dom::Patcher;
+use virtual_view_dom::{Patcher, Scheduler};
 
 struct App;
 
@@ -87,15 +87,15 @@ fn main() {
 
     let event_manager = EventManager::new();
 
-    let patcher = Rc::new(RefCell::new(Patcher::new(
+    let scheduler = Scheduler::new(Rc::new(RefCell::new(Patcher::new(
         document().get_element_by_id("app").unwrap().into(),
         document(),
         event_manager.clone(),
-    )));
+    ))));
 
     let _ = client.on("virtual_view.transaction", move |t: &Value| {
         let transaction = from_value(t.clone()).unwrap();
-        patcher.borrow_mut().patch(&transaction);
+        scheduler.schedule(&transaction);
         None
     });
 
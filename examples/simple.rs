@@ -11,13 +11,11 @@ use std::cell::RefCell;
 
 use serde_json::{from_value, Value};
 use stdweb::PromiseFuture;
-use stdweb::web::{document, INonElementParentNode, Node};
-use stdweb::web::html_element::InputElement;
-use stdweb::unstable::TryInto;
+use stdweb::web::{document, INonElementParentNode};
 
 use virtual_view::{Array, Children, Component, EventManager, Instance, Prop, Props, Renderer,
                    Updater, View};
-use virtual_view_dom::Patcher;
+use virtual_view_dom::{listen_dom_requests, listen_ref_requests, Patcher, VALUE_REQUEST};
 
 struct App;
 
@@ -43,17 +41,15 @@ impl App {
         });
         Prop::Null
     }
-    fn text_change(updater: &Updater, e: &mut Props) -> Prop {
+    fn text_change(updater: &Updater) -> Prop {
         let u = updater.clone();
 
         updater.send(
-            "virtual_view_dom.input.value",
-            json!({
-                "input_id": e.get("component_id").string().unwrap()
-            }),
-            move |props| {
-                if let Some(data) = props.as_object() {
-                    let value: Prop = data.get("value").unwrap().into();
+            VALUE_REQUEST,
+            json!({ "ref": "input" }),
+            move |data| {
+                if let Some(value) = data.get("value") {
+                    let value: Prop = value.into();
 
                     u.set_state(move |prev| {
                         let mut next = prev.clone();
@@ -103,10 +99,11 @@ impl Component for App {
             <div class="Component">
                 <input
                     type="text"
+                    ref="input"
                     value={ instance.state.get("text") }
                     oninput={ block {
                         let updater = instance.updater.clone();
-                        move |e: &mut Props| App::text_change(&updater, e)
+                        move |_: &mut Props| App::text_change(&updater)
                     } }
                 />
                 <{AddTodo} add_todo={ block {
@@ -204,11 +201,6 @@ impl Component for Footer {
     }
 }
 
-fn dom_node(id: &str) -> Option<Node> {
-    unsafe { PATCHER.as_ref().unwrap().borrow().node(id) }
-}
-static mut PATCHER: Option<Rc<RefCell<Patcher>>> = None;
-
 fn main() {
     stdweb::initialize();
 
@@ -222,38 +214,15 @@ fn main() {
         event_manager.clone(),
     )));
 
-    unsafe {
-        PATCHER = Some(patcher);
-    }
+    listen_ref_requests(&client, &patcher);
+    listen_dom_requests(&client, &patcher);
 
     let _ = client.on("virtual_view.transaction", move |t: &Value| {
         let transaction = from_value(t.clone()).unwrap();
-        unsafe {
-            PATCHER.as_ref().unwrap().borrow_mut().patch(&transaction);
-        }
+        patcher.borrow_mut().patch(&transaction);
         None
     });
 
-    let _ = client.on("virtual_view_dom.input.value", move |data: &Value| {
-        let value = if let Some(data) = data.as_object() {
-            let id = data.get("input_id").unwrap().as_str().unwrap();
-
-            if let Some(node) = dom_node(id) {
-                if let Ok(input) = TryInto::<InputElement>::try_into(node) {
-                    input.raw_value()
-                } else {
-                    String::new()
-                }
-            } else {
-                String::new()
-            }
-        } else {
-            String::new()
-        };
-
-        Some(json!({ "value": value }))
-    });
-
     let _renderer = Renderer::new(
         view! {
             <{App}/>
@@ -0,0 +1,285 @@
+use fnv::FnvHashSet;
+use serde_json::{Map, Value};
+use virtual_view::{view_id, Patch, RawView, Transaction};
+
+/// a step of a batch's patches, a remove only lands here when a later insert
+/// reuses its id, so it runs before that insert instead of with the removes
+#[derive(Debug, Clone, PartialEq)]
+pub enum BatchPatch {
+    Patch(Patch),
+    Remove(RawView),
+}
+
+impl BatchPatch {
+    #[inline]
+    pub fn is_remove(&self) -> bool {
+        match self {
+            &BatchPatch::Remove(_) => true,
+            _ => false,
+        }
+    }
+}
+
+/// transactions merged into one unit of work, a `Patch::Props` is coalesced
+/// into an earlier one for the same node when no structural patch lies between
+/// them, and nodes both inserted and removed within the batch are never created
+///
+/// removes run after every patch, except those of an id a later transaction
+/// inserts again, which keep their place before that insert
+pub struct Batch {
+    patches: Vec<(String, BatchPatch)>,
+    removes: Vec<(String, RawView)>,
+    events: Vec<(String, String, bool)>,
+}
+
+impl Batch {
+    #[inline]
+    pub fn new() -> Self {
+        Batch {
+            patches: Vec::new(),
+            removes: Vec::new(),
+            events: Vec::new(),
+        }
+    }
+
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.patches.is_empty() && self.removes.is_empty() && self.events.is_empty()
+    }
+
+    #[inline]
+    pub fn patches(&self) -> &[(String, BatchPatch)] {
+        &self.patches
+    }
+    #[inline]
+    pub fn removes(&self) -> &[(String, RawView)] {
+        &self.removes
+    }
+    #[inline]
+    pub fn events(&self) -> &[(String, String, bool)] {
+        &self.events
+    }
+
+    #[inline]
+    pub fn push(&mut self, transaction: &Transaction) {
+        for (id, patches) in transaction.patches() {
+            for patch in patches {
+                self.push_patch(id, patch);
+            }
+        }
+        for (id, view) in transaction.removes() {
+            self.push_remove(id, view);
+        }
+        for (id, events) in transaction.events() {
+            for (name, value) in events {
+                self.push_event(id, name, *value);
+            }
+        }
+    }
+
+    #[inline]
+    pub fn push_patch(&mut self, id: &String, patch: &Patch) {
+        if let &Patch::Props(ref prev_props, ref diff_props) = patch {
+            if let Some(index) = self.coalescable_props(id) {
+                let batch_patch = &mut self.patches[index].1;
+
+                if let &mut BatchPatch::Patch(Patch::Props(ref mut prev, ref mut diff)) =
+                    batch_patch
+                {
+                    merge_props(prev, diff, prev_props, diff_props);
+                    return;
+                }
+            }
+        }
+        match patch {
+            &Patch::Mount(_) => self.flush_removes(id),
+            &Patch::Insert(ref child_id, _, _) => self.flush_removes(child_id),
+            _ => (),
+        }
+        self.patches.push((id.clone(), BatchPatch::Patch(patch.clone())));
+    }
+
+    #[inline]
+    pub fn push_remove(&mut self, id: &String, view: &RawView) {
+        if !self.cancel_insert(id, view) {
+            self.removes.push((id.clone(), view.clone()));
+        }
+    }
+
+    #[inline]
+    pub fn push_event(&mut self, id: &String, name: &String, value: bool) {
+        self.events.push((id.clone(), name.clone(), value));
+    }
+
+    /// the last `Patch::Props` for `id` if only other props patches follow it
+    #[inline]
+    fn coalescable_props(&self, id: &String) -> Option<usize> {
+        for (index, &(ref patch_id, ref patch)) in self.patches.iter().enumerate().rev() {
+            match patch {
+                &BatchPatch::Patch(Patch::Props(..)) => if patch_id == id {
+                    return Some(index);
+                },
+                _ => return None,
+            }
+        }
+        None
+    }
+
+    /// moves the pending removes of `id` and its subtree in with the patches,
+    /// so the old nodes are gone before a new node takes the same id
+    #[inline]
+    fn flush_removes(&mut self, id: &String) {
+        let mut index = 0;
+
+        while index < self.removes.len() {
+            if is_subtree_id(id, &self.removes[index].0) {
+                let (remove_id, view) = self.removes.remove(index);
+                self.patches.push((remove_id, BatchPatch::Remove(view)));
+            } else {
+                index += 1;
+            }
+        }
+    }
+
+    /// drops the `Patch::Insert` that created `id` in this batch, along with
+    /// every later patch and every event for its subtree, when no later patch
+    /// depends on the positions of the parent's children
+    #[inline]
+    fn cancel_insert(&mut self, id: &String, view: &RawView) -> bool {
+        let index = match self.patches.iter().rposition(|&(_, ref patch)| match patch {
+            &BatchPatch::Patch(Patch::Insert(ref child_id, _, _)) => child_id == id,
+            _ => false,
+        }) {
+            Some(index) => index,
+            None => return false,
+        };
+        let parent_id = self.patches[index].0.clone();
+
+        if self.patches[index + 1..]
+            .iter()
+            .any(|&(ref i, ref patch)| i == &parent_id && is_positional(patch))
+        {
+            return false;
+        }
+
+        let mut ids = FnvHashSet::default();
+        collect_ids(id, view, &mut ids);
+
+        let mut later = self.patches.split_off(index + 1);
+        later.retain(|&(ref i, ref patch)| patch.is_remove() || !ids.contains(i));
+        self.patches.pop();
+        self.patches.extend(later);
+        self.events.retain(|&(ref i, _, _)| !ids.contains(i));
+
+        true
+    }
+}
+
+#[inline]
+fn is_positional(patch: &BatchPatch) -> bool {
+    match patch {
+        &BatchPatch::Patch(Patch::Insert(..)) | &BatchPatch::Patch(Patch::Order(..)) => true,
+        _ => false,
+    }
+}
+
+#[inline]
+fn is_subtree_id(id: &str, other_id: &str) -> bool {
+    other_id.starts_with(id)
+        && (other_id.len() == id.len() || other_id[id.len()..].starts_with('.'))
+}
+
+#[inline]
+fn collect_ids(id: &String, view: &RawView, ids: &mut FnvHashSet<String>) {
+    ids.insert(id.clone());
+
+    if let &RawView::Data { ref children, .. } = view {
+        for (index, child) in children.iter().enumerate() {
+            collect_ids(&view_id(id, child.key(), index), child, ids);
+        }
+    }
+}
+
+/// merges a later props patch into an earlier one, keeping the oldest
+/// previous value of every key so removals still know what to remove
+#[inline]
+fn merge_props(
+    batch_prev_props: &mut Map<String, Value>,
+    batch_diff_props: &mut Map<String, Value>,
+    prev_props: &Map<String, Value>,
+    diff_props: &Map<String, Value>,
+) {
+    for (key, value) in prev_props {
+        if !batch_prev_props.contains_key(key) {
+            batch_prev_props.insert(key.clone(), value.clone());
+        }
+    }
+    for (key, value) in diff_props {
+        batch_diff_props.insert(key.clone(), value.clone());
+    }
+}
+
+#[test]
+fn test_batch() {
+    use super::tests::props;
+
+    let id = "0".to_string();
+    let child_id = "0.1".to_string();
+    let item: RawView = (&view! { <li>{"a"}</li> }).into();
+
+    let mut batch = Batch::new();
+    batch.push_patch(
+        &id,
+        &Patch::Props(props(json!({})), props(json!({ "class": "a", "title": "a" }))),
+    );
+    batch.push_patch(
+        &id,
+        &Patch::Props(
+            props(json!({ "class": "a", "title": "a" })),
+            props(json!({ "class": "b", "title": null })),
+        ),
+    );
+    batch.push_patch(&id, &Patch::Insert(child_id.clone(), 0, item.clone()));
+    batch.push_remove(&child_id, &item);
+
+    assert_eq!(
+        batch.patches(),
+        &[
+            (
+                id,
+                BatchPatch::Patch(Patch::Props(
+                    props(json!({ "class": "a", "title": "a" })),
+                    props(json!({ "class": "b", "title": null })),
+                )),
+            ),
+        ]
+    );
+    assert!(batch.removes().is_empty());
+}
+
+#[test]
+fn test_batch_remove_then_insert() {
+    use virtual_view::Transaction;
+    use super::tests::memory_patcher;
+
+    let (_, root, mut patcher) = memory_patcher();
+    let list: RawView = (&view! { <ul><li>{"a"}</li></ul> }).into();
+    let mut transaction = Transaction::new();
+    transaction.mount("0", list);
+    patcher.patch(&transaction);
+
+    let mut removed = Transaction::new();
+    removed.remove("0.0", (&view! { <li>{"a"}</li> }).into());
+    let mut inserted = Transaction::new();
+    inserted.insert("0", "0.0", 0, (&view! { <li>{"b"}</li> }).into());
+
+    let mut batch = Batch::new();
+    batch.push(&removed);
+    batch.push(&inserted);
+    assert!(batch.removes().is_empty());
+    assert!(batch.patches()[0].1.is_remove());
+
+    patcher.patch_batch(&batch);
+    assert_eq!(root.inner_html(), "<ul><li>b</li></ul>");
+    assert!(patcher.node("0.0").is_some());
+}
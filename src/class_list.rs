@@ -0,0 +1,64 @@
+use serde_json::Value;
+
+use super::prop_kind::{attribute_name, is_present};
+
+/// whether `key` is the `class` prop, or its `className` alias
+#[inline]
+pub fn is_class(key: &str) -> bool {
+    attribute_name(key) == "class"
+}
+
+/// the class names of a `class` prop, a string of names, an array of those
+/// or a map of names to whether they are set, each name once in order
+#[inline]
+pub fn class_names(value: &Value) -> Vec<String> {
+    let mut names = Vec::new();
+    push_class_names(value, &mut names);
+    names
+}
+
+/// `value`'s class names joined with spaces, the `class` attribute it renders
+#[inline]
+pub fn class_string(value: &Value) -> String {
+    class_names(value).join(" ")
+}
+
+#[inline]
+fn push_class_names(value: &Value, names: &mut Vec<String>) {
+    match value {
+        &Value::String(ref string) => for name in string.split_whitespace() {
+            push_class_name(name, names);
+        },
+        &Value::Array(ref array) => for value in array {
+            push_class_names(value, names);
+        },
+        &Value::Object(ref map) => for (key, value) in map {
+            if is_present(value) {
+                for name in key.split_whitespace() {
+                    push_class_name(name, names);
+                }
+            }
+        },
+        &Value::Number(ref number) => push_class_name(&number.to_string(), names),
+        &Value::Null | &Value::Bool(_) => (),
+    }
+}
+
+#[inline]
+fn push_class_name(name: &str, names: &mut Vec<String>) {
+    if !names.iter().any(|n| n == name) {
+        names.push(name.into());
+    }
+}
+
+#[test]
+fn test_class_names() {
+    assert_eq!(class_names(&json!("a  b a")), vec!["a", "b"]);
+    assert_eq!(class_names(&json!(["a", null, false, ["b c"]])), vec!["a", "b", "c"]);
+    assert_eq!(
+        class_names(&json!({ "a": true, "b": false, "c d": 1 })),
+        vec!["a", "c", "d"]
+    );
+    assert_eq!(class_string(&json!(["a", { "b": true }])), "a b");
+    assert!(is_class("className"));
+}
@@ -0,0 +1,100 @@
+use serde_json::Value;
+
+use super::{Dom, NodesIds};
+use super::props::value_to_attribute;
+
+/// whether `key` is a controlled prop of elements of `kind`, applied as a
+/// live dom property instead of an attribute and put back after the user
+/// changes it, so the view stays the source of truth for form state
+#[inline]
+pub fn is_controlled(kind: &str, key: &str) -> bool {
+    match (kind, key) {
+        ("input", "value") | ("input", "checked") => true,
+        ("textarea", "value") | ("select", "value") => true,
+        ("option", "selected") => true,
+        _ => false,
+    }
+}
+
+/// whether `key` is a controlled prop of `node`, only asking the dom for the
+/// node's kind when `key` could be one
+#[inline]
+pub fn controls<D: Dom>(document: &D, node: &D::Node, key: &str) -> bool {
+    match key {
+        "value" | "checked" | "selected" => document
+            .node_kind(node)
+            .map_or(false, |kind| is_controlled(&kind, key)),
+        _ => false,
+    }
+}
+
+/// `value` as the property `key` holds, `value` a string and `checked` and
+/// `selected` a boolean
+#[inline]
+pub fn controlled_value(key: &str, value: &Value) -> Value {
+    if key == "value" {
+        match value {
+            &Value::Null => Value::String(String::new()),
+            value => Value::String(value_to_attribute(value)),
+        }
+    } else {
+        match value {
+            &Value::Bool(value) => Value::Bool(value),
+            &Value::Null => Value::Bool(false),
+            _ => Value::Bool(true),
+        }
+    }
+}
+
+#[inline]
+pub fn set_controlled<D: Dom>(
+    document: &D,
+    nodes_ids: &NodesIds<D>,
+    node: &D::Node,
+    key: &str,
+    value: &Value,
+) {
+    let value = controlled_value(key, value);
+    set_controlled_property(document, node, key, &value);
+    nodes_ids.set_controlled(node, key, value);
+}
+
+/// stops controlling `key` and resets it, `value` to empty and the others to
+/// false
+#[inline]
+pub fn remove_controlled<D: Dom>(
+    document: &D,
+    nodes_ids: &NodesIds<D>,
+    node: &D::Node,
+    key: &str,
+) {
+    nodes_ids.remove_controlled(node, key);
+    set_controlled_property(document, node, key, &controlled_value(key, &Value::Null));
+}
+
+/// puts back the controlled props of `node` that changed since they were
+/// set, returns how many
+#[inline]
+pub fn restore_controlled<D: Dom>(document: &D, nodes_ids: &NodesIds<D>, node: &D::Node) -> usize {
+    let props = match nodes_ids.controlled(node) {
+        Some(props) => props,
+        None => return 0,
+    };
+    let mut count = 0;
+
+    for (key, value) in &props {
+        if &document.property(node, key) != value {
+            set_controlled_property(document, node, key, value);
+            count += 1;
+        }
+    }
+    count
+}
+
+#[inline]
+fn set_controlled_property<D: Dom>(document: &D, node: &D::Node, key: &str, value: &Value) {
+    match value {
+        &Value::String(ref value) => document.set_value(node, value),
+        value => document.set_property(node, key, value),
+    }
+}
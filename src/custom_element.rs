@@ -0,0 +1,196 @@
+use std::any::Any;
+use std::rc::Rc;
+use std::cell::RefCell;
+
+use fnv::FnvHashMap;
+use messenger;
+use serde_json::{from_value, Value};
+use stdweb::PromiseFuture;
+use stdweb::web::{document, Document, Node};
+use virtual_view::{EventManager, Prop, Props, Renderer, View};
+
+use super::{Dom, NodeRef, Patcher, Scheduler};
+
+struct Mounted {
+    scheduler: Scheduler,
+    _renderer: Box<Any>,
+}
+
+/// a custom element rendering a view, with its own `Renderer` and a
+/// `Patcher` in its shadow root, so pages can use it like any other tag
+///
+/// each instance mounts when connected to the document and unmounts when
+/// disconnected, observed attributes are passed to `render` as props, numbers
+/// and booleans parsed, and a change remounts the instance with the new props
+pub struct CustomElement {
+    name: String,
+    observed_attributes: Vec<String>,
+    render: Rc<Fn(Props) -> View>,
+}
+
+impl CustomElement {
+    /// `name` must contain a `-`, like `vv-counter`
+    #[inline]
+    pub fn new<F>(name: &str, render: F) -> Self
+    where
+        F: 'static + Fn(Props) -> View,
+    {
+        CustomElement {
+            name: name.into(),
+            observed_attributes: Vec::new(),
+            render: Rc::new(render),
+        }
+    }
+
+    #[inline]
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+    #[inline]
+    pub fn observed_attributes(&self) -> &[String] {
+        &self.observed_attributes
+    }
+
+    #[inline]
+    pub fn observe(mut self, attribute: &str) -> Self {
+        self.observed_attributes.push(attribute.into());
+        self
+    }
+
+    /// registers the element with `customElements.define`
+    #[inline]
+    pub fn define(self) {
+        let element = Rc::new(self);
+        let mounted: Rc<RefCell<FnvHashMap<NodeRef, Mounted>>> = Rc::default();
+
+        let connected = {
+            let element = element.clone();
+            let mounted = mounted.clone();
+            move |node: Node| element.mount(&mounted, node)
+        };
+        let disconnected = {
+            let element = element.clone();
+            let mounted = mounted.clone();
+            move |node: Node| {
+                element.unmount(&mounted, &node);
+            }
+        };
+        let attribute_changed = {
+            let element = element.clone();
+            let mounted = mounted.clone();
+            move |node: Node| if element.unmount(&mounted, &node) {
+                element.mount(&mounted, node);
+            }
+        };
+
+        js! {
+            var name = @{&element.name},
+                observedAttributes = @{&element.observed_attributes},
+                connected = @{connected},
+                disconnected = @{disconnected},
+                attributeChanged = @{attribute_changed};
+
+            function CustomElement() {
+                return Reflect.construct(HTMLElement, [], CustomElement);
+            }
+            CustomElement.prototype = Object.create(HTMLElement.prototype);
+            CustomElement.prototype.constructor = CustomElement;
+            Object.setPrototypeOf(CustomElement, HTMLElement);
+
+            Object.defineProperty(CustomElement, "observedAttributes", {
+                get: function() {
+                    return observedAttributes;
+                }
+            });
+            CustomElement.prototype.connectedCallback = function() {
+                connected(this);
+            };
+            CustomElement.prototype.disconnectedCallback = function() {
+                disconnected(this);
+            };
+            CustomElement.prototype.attributeChangedCallback = function() {
+                attributeChanged(this);
+            };
+
+            customElements.define(name, CustomElement);
+        };
+    }
+
+    #[inline]
+    fn mount(&self, mounted: &RefCell<FnvHashMap<NodeRef, Mounted>>, node: Node) {
+        let document = document();
+        let props = self.props(&document, &node);
+        let (server, client, future) = messenger::unbounded_channel();
+        let event_manager = EventManager::new();
+        // without a shadow root the view still renders, only unisolated
+        let root = document.attach_shadow(&node).unwrap_or_else(|message| {
+            document.warn(&format!("{} mounted without a shadow root: {}", self.name, message));
+            node.clone()
+        });
+        let patcher = Patcher::new(root, document, event_manager.clone());
+        let scheduler = Scheduler::new(Rc::new(RefCell::new(patcher)));
+
+        let client_scheduler = scheduler.clone();
+        let _ = client.on("virtual_view.transaction", move |t: &Value| {
+            let transaction = from_value(t.clone()).unwrap();
+            client_scheduler.schedule(&transaction);
+            None
+        });
+
+        let renderer = Renderer::new((self.render)(props), event_manager, server);
+
+        PromiseFuture::spawn(future);
+
+        mounted.borrow_mut().insert(
+            <Document as Dom>::node_key(&node),
+            Mounted {
+                scheduler: scheduler,
+                _renderer: Box::new((renderer, client)),
+            },
+        );
+    }
+
+    /// returns false if `node` was not mounted
+    #[inline]
+    fn unmount(&self, mounted: &RefCell<FnvHashMap<NodeRef, Mounted>>, node: &Node) -> bool {
+        let key = <Document as Dom>::node_key(node);
+        let instance = match mounted.borrow_mut().remove(&key) {
+            Some(instance) => instance,
+            None => return false,
+        };
+
+        instance.scheduler.cancel();
+
+        let patcher = instance.scheduler.patcher();
+        let mut patcher = patcher.borrow_mut();
+        let root = patcher.root().clone();
+
+        patcher.teardown();
+        for child_node in patcher.document().child_nodes(&root) {
+            patcher.document().remove_child(&root, &child_node);
+        }
+
+        true
+    }
+
+    #[inline]
+    fn props(&self, document: &Document, node: &Node) -> Props {
+        let mut props = Props::new();
+
+        for name in &self.observed_attributes {
+            if let Some(value) = document.attribute(node, name) {
+                let prop: Prop = match value.parse::<f64>() {
+                    Ok(number) => number.into(),
+                    Err(_) => match value.as_str() {
+                        "true" => true.into(),
+                        "false" => false.into(),
+                        _ => value.clone().into(),
+                    },
+                };
+                props.set(name.as_str(), prop);
+            }
+        }
+
+        props
+    }
+}
@@ -0,0 +1,98 @@
+use std::hash::Hash;
+
+use serde_json::Value;
+use virtual_view::Prop;
+
+/// called with the event's composed path, from its target up through shadow
+/// roots and their hosts, and the event
+pub type DomListener<N> = Box<Fn(Vec<N>, Prop)>;
+pub type DomFrameCallback = Box<FnMut()>;
+
+/// an element's box relative to the viewport, from `getBoundingClientRect`
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct ClientRect {
+    pub left: f64,
+    pub top: f64,
+    pub width: f64,
+    pub height: f64,
+}
+
+pub trait Dom: Clone + 'static {
+    type Node: Clone + 'static;
+    type NodeKey: Clone + Eq + Hash + 'static;
+    type Listener;
+
+    fn node_key(node: &Self::Node) -> Self::NodeKey;
+
+    fn document_node(&self) -> Self::Node;
+    fn body(&self) -> Option<Self::Node>;
+    fn element_by_id(&self, id: &str) -> Option<Self::Node>;
+
+    fn node_kind(&self, node: &Self::Node) -> Option<String>;
+    fn is_comment(&self, node: &Self::Node) -> bool;
+    fn namespace_uri(&self, node: &Self::Node) -> Option<String>;
+    fn text_content(&self, node: &Self::Node) -> String;
+    fn attribute(&self, node: &Self::Node, key: &str) -> Option<String>;
+    fn attribute_names(&self, node: &Self::Node) -> Vec<String>;
+    fn outer_html(&self, node: &Self::Node) -> String;
+
+    fn create_element(&self, kind: &str) -> Self::Node;
+    fn create_element_ns(&self, namespace: &str, kind: &str) -> Self::Node;
+    fn create_text_node(&self, text: &str) -> Self::Node;
+    /// attaches an open shadow root to `host`, a `Patcher` can use it as its
+    /// root to keep its styles isolated, errors with what the dom threw when
+    /// `host` can not have one
+    fn attach_shadow(&self, host: &Self::Node) -> Result<Self::Node, String>;
+    fn set_inner_html(&self, node: &Self::Node, html: &str);
+    fn set_text_content(&self, node: &Self::Node, text: &str);
+
+    fn parent_node(&self, node: &Self::Node) -> Option<Self::Node>;
+    fn child_nodes(&self, node: &Self::Node) -> Vec<Self::Node>;
+    fn append_child(&self, parent: &Self::Node, child: &Self::Node);
+    fn insert_before(&self, parent: &Self::Node, child: &Self::Node, reference: &Self::Node);
+    fn remove_child(&self, parent: &Self::Node, child: &Self::Node);
+    fn replace_child(&self, parent: &Self::Node, new_child: &Self::Node, old_child: &Self::Node);
+
+    fn set_attribute(&self, node: &Self::Node, key: &str, value: &str);
+    fn remove_attribute(&self, node: &Self::Node, key: &str);
+    fn set_attribute_ns(&self, node: &Self::Node, namespace: &str, key: &str, value: &str);
+    fn remove_attribute_ns(&self, node: &Self::Node, namespace: &str, local_name: &str);
+    fn property(&self, node: &Self::Node, key: &str) -> Value;
+    fn set_property(&self, node: &Self::Node, key: &str, value: &Value);
+    /// sets the `value` property of a form element, keeping the caret and
+    /// selection where they were if it has focus
+    fn set_value(&self, node: &Self::Node, value: &str);
+    /// sets the style property `name`, in kebab case, custom properties too
+    fn set_style(&self, node: &Self::Node, name: &str, value: &str, important: bool);
+    fn remove_style(&self, node: &Self::Node, name: &str);
+    fn add_class(&self, node: &Self::Node, name: &str);
+    fn remove_class(&self, node: &Self::Node, name: &str);
+
+    fn focus(&self, node: &Self::Node);
+    fn blur(&self, node: &Self::Node);
+    /// selects the text of an input or textarea
+    fn select(&self, node: &Self::Node);
+    fn scroll_into_view(&self, node: &Self::Node);
+    /// clicks `node` like the user would, dispatching a `click` event
+    fn click(&self, node: &Self::Node);
+    fn bounding_client_rect(&self, node: &Self::Node) -> ClientRect;
+
+    fn add_event_listener(
+        &self,
+        target: &Self::Node,
+        name: &str,
+        listener: DomListener<Self::Node>,
+    ) -> Self::Listener;
+    fn remove_event_listener(&self, target: &Self::Node, name: &str, listener: &Self::Listener);
+
+    /// calls `callback` once before the next repaint
+    fn request_animation_frame(&self, callback: DomFrameCallback);
+    /// milliseconds from a monotonic clock, used to budget time sliced patching
+    fn now(&self) -> f64;
+
+    /// takes the message of the last exception a dom operation threw, they
+    /// are caught so one bad attribute or property does not abort patching
+    fn take_exception(&self) -> Option<String>;
+
+    fn warn(&self, message: &str);
+}
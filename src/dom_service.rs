@@ -0,0 +1,169 @@
+use std::rc::Rc;
+use std::cell::RefCell;
+
+use messenger::Messenger;
+use serde_json::{Map, Number, Value};
+
+use super::{ClientRect, Dom, Patcher};
+
+/// reads the `value` of an input, textarea or select, `{ "value": "" }`
+pub const VALUE_REQUEST: &'static str = "virtual_view_dom.value";
+pub const FOCUS_REQUEST: &'static str = "virtual_view_dom.focus";
+pub const BLUR_REQUEST: &'static str = "virtual_view_dom.blur";
+/// selects the text of an input or textarea
+pub const SELECT_REQUEST: &'static str = "virtual_view_dom.select";
+pub const SCROLL_INTO_VIEW_REQUEST: &'static str = "virtual_view_dom.scroll_into_view";
+pub const CLICK_REQUEST: &'static str = "virtual_view_dom.click";
+/// reads the scroll position, `{ "left": 0, "top": 0 }`
+pub const SCROLL_REQUEST: &'static str = "virtual_view_dom.scroll";
+/// scrolls to `left` and `top`, either may be left out
+pub const SET_SCROLL_REQUEST: &'static str = "virtual_view_dom.set_scroll";
+/// reads the bounding client rect, `{ "left", "top", "width", "height" }`
+pub const RECT_REQUEST: &'static str = "virtual_view_dom.rect";
+
+/// answers the dom requests above from `messenger` for the nodes `patcher`
+/// mounted, so components can `Updater::send` them without any js
+///
+/// each request names its node by view id, `{ "id": "0.1" }` or just
+/// `"0.1"`, or by ref prop, `{ "ref": "input" }`, and is answered with an
+/// object, which is what `Updater::send` passes on, commands without a
+/// result answer `{ "done": true }`, `null` when there is no such node
+#[inline]
+pub fn listen_dom_requests<D: Dom>(
+    messenger: &Messenger<Value>,
+    patcher: &Rc<RefCell<Patcher<D>>>,
+) {
+    listen(messenger, patcher, VALUE_REQUEST, |document, node, _| {
+        let mut value = Map::new();
+        value.insert("value".into(), document.property(node, "value"));
+        Value::Object(value)
+    });
+    listen(messenger, patcher, FOCUS_REQUEST, |document, node, _| {
+        document.focus(node);
+        done()
+    });
+    listen(messenger, patcher, BLUR_REQUEST, |document, node, _| {
+        document.blur(node);
+        done()
+    });
+    listen(messenger, patcher, SELECT_REQUEST, |document, node, _| {
+        document.select(node);
+        done()
+    });
+    listen(messenger, patcher, SCROLL_INTO_VIEW_REQUEST, |document, node, _| {
+        document.scroll_into_view(node);
+        done()
+    });
+    listen(messenger, patcher, CLICK_REQUEST, |document, node, _| {
+        document.click(node);
+        done()
+    });
+    listen(messenger, patcher, SCROLL_REQUEST, |document, node, _| {
+        scroll_position(document, node)
+    });
+    listen(messenger, patcher, SET_SCROLL_REQUEST, |document, node, data| {
+        for &(key, property) in &[("left", "scrollLeft"), ("top", "scrollTop")] {
+            match data.get(key) {
+                Some(value) if value.is_number() => document.set_property(node, property, value),
+                _ => (),
+            }
+        }
+        scroll_position(document, node)
+    });
+    listen(messenger, patcher, RECT_REQUEST, |document, node, _| {
+        rect_to_value(&document.bounding_client_rect(node))
+    });
+}
+
+#[inline]
+fn listen<D, F>(
+    messenger: &Messenger<Value>,
+    patcher: &Rc<RefCell<Patcher<D>>>,
+    name: &str,
+    handler: F,
+) where
+    D: Dom,
+    F: 'static + Fn(&D, &D::Node, &Value) -> Value,
+{
+    let patcher = patcher.clone();
+
+    let _ = messenger.on(name, move |data: &Value| {
+        let patcher = patcher.borrow();
+
+        Some(match request_node(&patcher, data) {
+            Some(node) => handler(patcher.document(), &node, data),
+            None => Value::Null,
+        })
+    });
+}
+
+/// the node a request names by view id or ref
+#[inline]
+fn request_node<D: Dom>(patcher: &Patcher<D>, data: &Value) -> Option<D::Node> {
+    match data {
+        &Value::String(ref id) => patcher.node(id),
+        data => match (data.get("id"), data.get("ref")) {
+            (Some(&Value::String(ref id)), _) => patcher.node(id),
+            (_, Some(&Value::String(ref name))) => patcher.ref_node(name),
+            _ => None,
+        },
+    }
+}
+
+/// the answer to a command
+#[inline]
+fn done() -> Value {
+    let mut value = Map::new();
+    value.insert("done".into(), Value::Bool(true));
+    Value::Object(value)
+}
+
+#[inline]
+fn scroll_position<D: Dom>(document: &D, node: &D::Node) -> Value {
+    let mut position = Map::new();
+    position.insert("left".into(), document.property(node, "scrollLeft"));
+    position.insert("top".into(), document.property(node, "scrollTop"));
+    Value::Object(position)
+}
+
+#[inline]
+fn rect_to_value(rect: &ClientRect) -> Value {
+    let mut value = Map::new();
+
+    for &(key, number) in &[
+        ("left", rect.left),
+        ("top", rect.top),
+        ("width", rect.width),
+        ("height", rect.height),
+    ] {
+        let number = Number::from_f64(number).map_or(Value::Null, Value::Number);
+        value.insert(key.into(), number);
+    }
+
+    Value::Object(value)
+}
+
+#[test]
+fn test_request_node() {
+    use virtual_view::Patch;
+
+    use super::Batch;
+    use super::tests::memory_patcher;
+
+    let (_, root, mut patcher) = memory_patcher();
+    let id = "0".to_string();
+
+    let mut batch = Batch::new();
+    batch.push_patch(&id, &Patch::Mount((&view! { <input ref="input"/> }).into()));
+    patcher.try_patch_batch(&batch).unwrap();
+
+    let node = root.child(0);
+    assert_eq!(request_node(&patcher, &json!("0")), node);
+    assert_eq!(request_node(&patcher, &json!({ "id": "0" })), node);
+    assert_eq!(request_node(&patcher, &json!({ "ref": "input" })), node);
+    assert_eq!(request_node(&patcher, &json!({ "id": "1" })), None);
+    assert_eq!(
+        rect_to_value(&ClientRect::default()),
+        json!({ "left": 0.0, "top": 0.0, "width": 0.0, "height": 0.0 })
+    );
+}
@@ -1,52 +1,137 @@
-use stdweb::Reference;
-use stdweb::web::{Document, Node};
-use stdweb::unstable::TryInto;
+use std::rc::Rc;
+use std::cell::RefCell;
+
+use stdweb::web::Document;
 use fnv::FnvHashMap;
-use virtual_view::{EventManager, Props};
+use virtual_view::{EventManager, Prop, Props};
+
+use super::{Dom, NodesIds, Recorder};
+use super::controlled::restore_controlled;
 
-use super::{js_value_to_prop, NodesIds};
+/// the events after which controlled props are put back
+const CONTROLLED_EVENTS: [&'static str; 2] = ["input", "change"];
 
-pub struct Events {
+/// delegates dom events to the `EventManager`, one listener per event name
+/// on each root, the patcher's root and the containers of its portals, so
+/// patchers and other code sharing a page never see each other's events
+pub struct Events<D: Dom = Document> {
+    roots: Rc<RefCell<Vec<D::Node>>>,
     listening: FnvHashMap<String, usize>,
-    listening_handlers: FnvHashMap<String, FnvHashMap<String, Reference>>,
+    listening_handlers: FnvHashMap<String, Vec<(D::Node, D::Listener)>>,
+    listening_controlled: bool,
     event_manager: EventManager,
+    recorder: Rc<RefCell<Option<Recorder>>>,
 }
 
-impl Events {
+impl<D: Dom> Events<D> {
     #[inline(always)]
-    pub fn new(event_manager: EventManager) -> Self {
+    pub fn new(root: D::Node, event_manager: EventManager) -> Self {
         Events {
+            roots: Rc::new(RefCell::new(vec![root])),
             listening: FnvHashMap::default(),
             listening_handlers: FnvHashMap::default(),
+            listening_controlled: false,
             event_manager: event_manager,
+            recorder: Rc::new(RefCell::new(None)),
         }
     }
 
     #[inline]
-    pub fn listen(
-        &mut self,
-        name: &str,
-        id: &str,
-        node: &Node,
-        nodes_ids: &NodesIds,
-        document: &Document,
-    ) {
+    pub fn roots(&self) -> Vec<D::Node> {
+        self.roots.borrow().clone()
+    }
+    /// delegates events from `root` as well, with every name listened to so
+    /// far, returns how many listeners were added
+    #[inline]
+    pub fn add_root(&mut self, root: D::Node, nodes_ids: &NodesIds<D>, document: &D) -> usize {
+        let key = D::node_key(&root);
+
+        if self.roots.borrow().iter().any(|r| D::node_key(r) == key) {
+            return 0;
+        }
+        self.roots.borrow_mut().push(root.clone());
+
+        let names: Vec<String> = self.listening.keys().cloned().collect();
+        for name in &names {
+            self.add_event_listener(name, &root, nodes_ids, document);
+        }
+        names.len()
+    }
+    /// stops delegating events from `root`, returns how many listeners were
+    /// removed
+    #[inline]
+    pub fn remove_root(&mut self, root: &D::Node, document: &D) -> usize {
+        let key = D::node_key(root);
+        let mut count = 0;
+
+        self.roots.borrow_mut().retain(|r| D::node_key(r) != key);
+
+        for (name, listeners) in self.listening_handlers.iter_mut() {
+            listeners.retain(|&(ref node, ref listener_reference)| {
+                if D::node_key(node) == key {
+                    document.remove_event_listener(node, name, listener_reference);
+                    count += 1;
+                    false
+                } else {
+                    true
+                }
+            });
+        }
+        count
+    }
+
+    #[inline]
+    pub fn recorder(&self) -> Option<Recorder> {
+        self.recorder.borrow().clone()
+    }
+    #[inline]
+    pub fn set_recorder(&self, recorder: Option<Recorder>) {
+        *self.recorder.borrow_mut() = recorder;
+    }
+
+    /// sends `event` named `name` for the view `id` to the `EventManager`
+    #[inline]
+    pub fn dispatch(&self, document: &D, id: &str, name: &str, event: Prop) {
+        Self::dispatch_event(document, &self.event_manager, &self.recorder, id, name, event);
+    }
+
+    /// returns how many listeners for `name` were added, one per root the
+    /// first time `name` is listened to
+    #[inline]
+    pub fn listen(&mut self, name: &str, nodes_ids: &NodesIds<D>, document: &D) -> usize {
         if !self.listening.contains_key(name) {
             self.listening.insert(name.into(), 1);
-            self.add_event_listener(name, id, node, nodes_ids, document);
+
+            let roots = self.roots();
+            for root in &roots {
+                self.add_event_listener(name, root, nodes_ids, document);
+            }
+            roots.len()
         } else {
             self.listening.get_mut(name).map(|count| *count += 1);
+            0
         }
     }
+    /// listens to `input` and `change` for good once a node is controlled, so
+    /// its props are put back after an edit even when no view handles them,
+    /// returns how many listeners were added
     #[inline]
-    pub fn unlisten(
-        &mut self,
-        name: &str,
-        id: &str,
-        node: &Node,
-        nodes_ids: &NodesIds,
-        document: &Document,
-    ) {
+    pub fn listen_controlled(&mut self, nodes_ids: &NodesIds<D>, document: &D) -> usize {
+        if self.listening_controlled {
+            return 0;
+        }
+        self.listening_controlled = true;
+
+        let mut count = 0;
+        for name in CONTROLLED_EVENTS.iter() {
+            count += self.listen(name, nodes_ids, document);
+        }
+        count
+    }
+    /// returns how many listeners for `name` were removed, one per root once
+    /// nothing listens to `name`
+    #[inline]
+    pub fn unlisten(&mut self, name: &str, document: &D) -> usize {
         let count = if let Some(count) = self.listening.get_mut(name) {
             *count -= 1;
             Some(*count)
@@ -55,77 +140,140 @@ impl Events {
         };
         if count == Some(0) {
             self.listening.remove(name);
-            self.remove_event_listener(name, id, node, nodes_ids, document);
+            self.remove_event_listeners(name, document)
+        } else {
+            0
         }
     }
 
+    /// removes every listener this added, returns how many
     #[inline]
-    fn handle(event_manager: &EventManager, nodes_ids: &NodesIds, event: Reference) {
-        let target: Node = js! {
-            return @{event.as_ref()}.target;
-        }.try_into()
-            .unwrap();
-
-        if let Some(id) = nodes_ids.id(&target) {
-            let name: String = js! {
-                return "on" + @{event.as_ref()}.type;
-            }.try_into()
-                .unwrap();
-
-            let mut props = Props::new();
-            props.set("name", name);
-            props.set("event", js_value_to_prop(event.try_into().unwrap()));
-            event_manager.dispatch(&id, &mut props);
+    pub fn clear(&mut self, document: &D) -> usize {
+        let names: Vec<String> = self.listening_handlers.keys().cloned().collect();
+        let mut count = 0;
+
+        for name in &names {
+            count += self.remove_event_listeners(name, document);
         }
+        self.listening.clear();
+        self.listening_controlled = false;
+
+        count
     }
 
+    /// dispatches to the innermost view on `path`, the part of the event's
+    /// composed path inside the root, which goes through shadow roots to
+    /// their hosts so events from inside a shadow tree still find their view
     #[inline]
-    fn add_event_listener(
-        &mut self,
+    fn handle(
+        document: &D,
+        event_manager: &EventManager,
+        recorder: &Rc<RefCell<Option<Recorder>>>,
+        nodes_ids: &NodesIds<D>,
         name: &str,
-        id: &str,
-        _node: &Node,
-        nodes_ids: &NodesIds,
-        document: &Document,
+        path: &[D::Node],
+        event: Prop,
     ) {
-        let event_manager = self.event_manager.clone();
-        let nodes_ids = nodes_ids.clone();
-        let listener = move |e| Self::handle(&event_manager, &nodes_ids, e);
-        let listener_reference: Reference = js! {
-            var type = @{name},
-                document = @{document},
-                listener = @{listener};
+        let handle = path.iter().filter_map(|node| nodes_ids.handle(node)).next();
 
-            document.addEventListener(type, listener);
-            return listener;
-        }.try_into()
-            .unwrap();
+        if let Some(id) = handle.and_then(|handle| nodes_ids.handle_id(handle)) {
+            Self::dispatch_event(document, event_manager, recorder, &id, name, event);
+        }
+    }
 
-        self.listening_handlers
-            .entry(name.into())
-            .or_insert(FnvHashMap::default())
-            .insert(id.into(), listener_reference);
+    /// puts back the controlled props of `target` on the next frame, unless a
+    /// patch from the event's handlers set them to what the user entered
+    #[inline]
+    fn restore_controlled(document: &D, nodes_ids: &NodesIds<D>, target: &D::Node) {
+        if nodes_ids.controlled(target).is_some() {
+            let frame_document = document.clone();
+            let nodes_ids = nodes_ids.clone();
+            let target = target.clone();
+
+            document.request_animation_frame(Box::new(move || {
+                restore_controlled(&frame_document, &nodes_ids, &target);
+            }));
+        }
     }
+
     #[inline]
-    fn remove_event_listener(
-        &mut self,
-        name: &str,
+    fn dispatch_event(
+        document: &D,
+        event_manager: &EventManager,
+        recorder: &Rc<RefCell<Option<Recorder>>>,
         id: &str,
-        _node: &Node,
-        _nodes_ids: &NodesIds,
-        document: &Document,
+        name: &str,
+        event: Prop,
     ) {
-        let listener_reference = self.listening_handlers
-            .entry(name.into())
-            .or_insert(FnvHashMap::default())
-            .get(id);
+        if let Some(ref recorder) = *recorder.borrow() {
+            if let Err(error) = recorder.record_event(id, name, &event) {
+                document.warn(&error.to_string());
+            }
+        }
 
-        js! {
-            var type = @{name},
-                document = @{document},
-                listener = @{listener_reference.as_ref()};
+        let mut props = Props::new();
+        props.set("name", format!("on{}", name));
+        props.set("event", event);
+        event_manager.dispatch(id, &mut props);
+    }
 
-            document.removeEventListener(type, listener);
+    #[inline]
+    fn add_event_listener(
+        &mut self,
+        name: &str,
+        root: &D::Node,
+        nodes_ids: &NodesIds<D>,
+        document: &D,
+    ) {
+        let event_manager = self.event_manager.clone();
+        let recorder = self.recorder.clone();
+        let nodes_ids = nodes_ids.clone();
+        let roots = self.roots.clone();
+        let root_key = D::node_key(root);
+        let listener_name = name.to_string();
+        let listener_document = document.clone();
+        let listener = move |path: Vec<D::Node>, event: Prop| {
+            // roots can nest, only the innermost one on the path handles it
+            let index = match root_index::<D>(&roots.borrow(), &path) {
+                Some(index) if D::node_key(&path[index]) == root_key => index,
+                _ => return,
+            };
+            Self::handle(
+                &listener_document,
+                &event_manager,
+                &recorder,
+                &nodes_ids,
+                &listener_name,
+                &path[..index],
+                event,
+            );
+            Self::restore_controlled(&listener_document, &nodes_ids, &path[0]);
         };
+        let listener_reference = document.add_event_listener(root, name, Box::new(listener));
+
+        self.listening_handlers
+            .entry(name.into())
+            .or_insert_with(Vec::new)
+            .push((root.clone(), listener_reference));
     }
+    #[inline]
+    fn remove_event_listeners(&mut self, name: &str, document: &D) -> usize {
+        if let Some(listeners) = self.listening_handlers.remove(name) {
+            for &(ref root, ref listener_reference) in &listeners {
+                document.remove_event_listener(root, name, listener_reference);
+            }
+            listeners.len()
+        } else {
+            0
+        }
+    }
+}
+
+/// the index of the first of `roots` on `path`
+#[inline]
+fn root_index<D: Dom>(roots: &[D::Node], path: &[D::Node]) -> Option<usize> {
+    path.iter().position(|node| {
+        let key = D::node_key(node);
+        roots.iter().any(|root| D::node_key(root) == key)
+    })
 }
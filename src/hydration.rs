@@ -0,0 +1,124 @@
+use std::error::Error;
+use std::fmt;
+
+use virtual_view::RawView;
+
+use super::{PatchError, ToHtmlString};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum HydrationPolicy {
+    /// warn and patch the existing node in place
+    Repair,
+    /// warn and replace the whole mismatched subtree with freshly created nodes
+    Remount,
+    /// stop hydrating and return the first mismatch as an error
+    Fail,
+}
+
+impl Default for HydrationPolicy {
+    #[inline(always)]
+    fn default() -> Self {
+        HydrationPolicy::Repair
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum HydrationMismatchKind {
+    Kind,
+    Text,
+    Attribute(String),
+    MissingChild,
+    ExtraChild,
+}
+
+impl fmt::Display for HydrationMismatchKind {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            &HydrationMismatchKind::Kind => write!(f, "different kind"),
+            &HydrationMismatchKind::Text => write!(f, "different text"),
+            &HydrationMismatchKind::Attribute(ref name) => {
+                write!(f, "different attribute {:?}", name)
+            }
+            &HydrationMismatchKind::MissingChild => write!(f, "missing child"),
+            &HydrationMismatchKind::ExtraChild => write!(f, "extra child"),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct HydrationMismatch {
+    pub id: String,
+    pub kind: HydrationMismatchKind,
+    pub expected: Option<RawView>,
+    pub actual: Option<String>,
+}
+
+impl HydrationMismatch {
+    #[inline]
+    pub fn new(
+        id: &str,
+        kind: HydrationMismatchKind,
+        expected: Option<&RawView>,
+        actual: Option<String>,
+    ) -> Self {
+        HydrationMismatch {
+            id: id.into(),
+            kind: kind,
+            expected: expected.map(Clone::clone),
+            actual: actual,
+        }
+    }
+}
+
+impl fmt::Display for HydrationMismatch {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "hydration mismatch at {:?}, {}: expected {} found {}",
+            self.id,
+            self.kind,
+            self.expected
+                .as_ref()
+                .map(ToHtmlString::to_html_string)
+                .unwrap_or_else(|| "nothing".into()),
+            self.actual
+                .as_ref()
+                .map(Clone::clone)
+                .unwrap_or_else(|| "nothing".into()),
+        )
+    }
+}
+
+#[derive(Debug, Clone)]
+pub enum HydrationError {
+    /// the markup differs from the views and the policy is `Fail`
+    Mismatch(HydrationMismatch),
+    /// a patch other than the mount, or a remove, failed and the patcher's
+    /// `PatchErrorPolicy` does not continue past it
+    Patch(PatchError),
+}
+
+impl From<HydrationMismatch> for HydrationError {
+    #[inline(always)]
+    fn from(mismatch: HydrationMismatch) -> Self {
+        HydrationError::Mismatch(mismatch)
+    }
+}
+
+impl From<PatchError> for HydrationError {
+    #[inline(always)]
+    fn from(error: PatchError) -> Self {
+        HydrationError::Patch(error)
+    }
+}
+
+impl fmt::Display for HydrationError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            &HydrationError::Mismatch(ref mismatch) => mismatch.fmt(f),
+            &HydrationError::Patch(ref error) => error.fmt(f),
+        }
+    }
+}
+
+impl Error for HydrationError {}
@@ -1,5 +1,6 @@
 extern crate fnv;
 extern crate futures;
+extern crate messenger;
 extern crate serde;
 #[cfg_attr(test, macro_use)]
 extern crate serde_json;
@@ -9,13 +10,68 @@ extern crate stdweb;
 extern crate virtual_view;
 
 mod utils;
+mod batch;
+mod class_list;
+mod controlled;
+#[cfg(feature = "custom_elements")]
+mod custom_element;
+mod dom;
+mod dom_service;
 mod events;
+mod hydration;
+mod memory;
+mod namespace;
+mod node_builder;
 mod node_ref;
 mod nodes_ids;
+mod patch_error;
+mod patch_future;
+mod patch_report;
+mod patch_task;
 mod patcher;
+mod portal;
+mod prop_kind;
+mod props;
+mod raw_html;
+mod recorder;
+mod recording;
+mod refs;
+mod reorder;
+mod rollback;
+mod scheduler;
+mod style;
+#[cfg(test)]
+mod tests;
+mod text_mode;
+mod web_dom;
 
 pub use self::utils::{js_value_to_array, js_value_to_prop, js_value_to_props, ToHtmlString};
+pub use self::batch::{Batch, BatchPatch};
+#[cfg(feature = "custom_elements")]
+pub use self::custom_element::CustomElement;
+pub use self::dom::{ClientRect, Dom, DomFrameCallback, DomListener};
+pub use self::dom_service::{listen_dom_requests, BLUR_REQUEST, CLICK_REQUEST, FOCUS_REQUEST,
+                           RECT_REQUEST, SCROLL_INTO_VIEW_REQUEST, SCROLL_REQUEST,
+                           SELECT_REQUEST, SET_SCROLL_REQUEST, VALUE_REQUEST};
 pub use self::events::Events;
+pub use self::hydration::{HydrationError, HydrationMismatch, HydrationMismatchKind,
+                          HydrationPolicy};
+pub use self::memory::{parse_html, MemoryDocument, MemoryNode};
+pub use self::namespace::{attribute_namespace, Namespace, HTML_NAMESPACE, MATHML_NAMESPACE,
+                          SVG_NAMESPACE, XLINK_NAMESPACE, XMLNS_NAMESPACE, XML_NAMESPACE};
+pub use self::node_builder::NodeBuilder;
 pub use self::node_ref::NodeRef;
-pub use self::nodes_ids::NodesIds;
+pub use self::nodes_ids::{NodeId, NodesIds, ViewId};
+pub use self::patch_error::{PatchError, PatchErrorPolicy};
+pub use self::patch_future::PatchFuture;
+pub use self::patch_report::PatchReport;
+pub use self::patch_task::PatchTask;
 pub use self::patcher::Patcher;
+pub use self::portal::PORTAL_PROP;
+pub use self::prop_kind::{attribute_name, prop_kind, PropKind};
+pub use self::raw_html::RAW_HTML_PROP;
+pub use self::recorder::Recorder;
+pub use self::recording::{Record, Recording, RecordingError};
+pub use self::refs::{listen_ref_requests, RefChange, RefChangeKind, REF_PROP, REF_REQUEST};
+pub use self::scheduler::Scheduler;
+pub use self::text_mode::TextMode;
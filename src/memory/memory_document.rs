@@ -0,0 +1,425 @@
+use std::mem;
+use std::rc::Rc;
+use std::cell::RefCell;
+
+use serde_json::Value;
+use virtual_view::Prop;
+
+use super::super::{ClientRect, Dom, DomFrameCallback, DomListener};
+use super::super::namespace::{attribute_local_name, attribute_namespace};
+use super::{parse_html, MemoryNode};
+
+struct MemoryListener {
+    id: usize,
+    target: MemoryNode,
+    name: String,
+    listener: Rc<DomListener<MemoryNode>>,
+}
+
+struct MemoryDocumentInner {
+    node: MemoryNode,
+    next_listener_id: usize,
+    listeners: Vec<MemoryListener>,
+    frames: Vec<DomFrameCallback>,
+    time: f64,
+    exception: Option<String>,
+    warnings: Vec<String>,
+    active_element: Option<MemoryNode>,
+}
+
+#[derive(Clone)]
+pub struct MemoryDocument(Rc<RefCell<MemoryDocumentInner>>);
+
+impl MemoryDocument {
+    #[inline]
+    pub fn new() -> Self {
+        MemoryDocument(Rc::new(RefCell::new(MemoryDocumentInner {
+            node: MemoryNode::new_document(),
+            next_listener_id: 0,
+            listeners: Vec::new(),
+            frames: Vec::new(),
+            time: 0.0,
+            exception: None,
+            warnings: Vec::new(),
+            active_element: None,
+        })))
+    }
+
+    #[inline]
+    pub fn listener_count(&self) -> usize {
+        self.0.borrow().listeners.len()
+    }
+
+    #[inline]
+    pub fn pending_frames(&self) -> usize {
+        self.0.borrow().frames.len()
+    }
+
+    /// runs the animation frame callbacks requested so far, callbacks they
+    /// request in turn wait for the next call, returns how many ran
+    #[inline]
+    pub fn run_animation_frames(&self) -> usize {
+        let frames = mem::replace(&mut self.0.borrow_mut().frames, Vec::new());
+        let count = frames.len();
+
+        for mut callback in frames {
+            callback();
+        }
+
+        count
+    }
+
+    /// moves the clock returned by `Dom::now` forward, it never moves on its own
+    #[inline]
+    pub fn advance_time(&self, ms: f64) {
+        self.0.borrow_mut().time += ms;
+    }
+
+    #[inline]
+    pub fn warnings(&self) -> Vec<String> {
+        self.0.borrow().warnings.clone()
+    }
+
+    /// the node last given focus by `Dom::focus`, unless blurred since
+    #[inline]
+    pub fn active_element(&self) -> Option<MemoryNode> {
+        self.0.borrow().active_element.clone()
+    }
+
+    /// dispatches a bubbling, composed event from `target` up to the document
+    /// node, going from shadow roots to their hosts, calling every listener
+    /// registered for `name` along the way
+    #[inline]
+    pub fn dispatch_event(&self, target: &MemoryNode, name: &str, event: Prop) {
+        let mut path = Vec::new();
+        let mut current = Some(target.clone());
+
+        while let Some(node) = current {
+            current = node.parent().or_else(|| node.host());
+            path.push(node);
+        }
+
+        for node in &path {
+            let listeners: Vec<_> = self.0
+                .borrow()
+                .listeners
+                .iter()
+                .filter(|l| &l.target == node && l.name == name)
+                .map(|l| l.listener.clone())
+                .collect();
+
+            for listener in listeners {
+                listener(path.clone(), event.clone());
+            }
+        }
+    }
+
+    /// records an `InvalidCharacterError` like a browser's `setAttribute`
+    /// throws for names that can not be attributes
+    #[inline]
+    fn valid_attribute_name(&self, key: &str) -> bool {
+        let valid = !key.is_empty()
+            && !key.chars().any(|c| {
+                c.is_whitespace() || c == '"' || c == '\'' || c == '>' || c == '/' || c == '='
+            });
+
+        if !valid {
+            self.0.borrow_mut().exception = Some(format!(
+                "InvalidCharacterError: {:?} is not a valid attribute name",
+                key
+            ));
+        }
+        valid
+    }
+}
+
+impl Dom for MemoryDocument {
+    type Node = MemoryNode;
+    type NodeKey = usize;
+    type Listener = usize;
+
+    #[inline]
+    fn node_key(node: &MemoryNode) -> usize {
+        node.id()
+    }
+
+    #[inline]
+    fn document_node(&self) -> MemoryNode {
+        self.0.borrow().node.clone()
+    }
+    #[inline]
+    fn body(&self) -> Option<MemoryNode> {
+        find_node(&self.document_node(), &|node| {
+            node.kind().map_or(false, |kind| kind.eq_ignore_ascii_case("body"))
+        })
+    }
+    #[inline]
+    fn element_by_id(&self, id: &str) -> Option<MemoryNode> {
+        find_node(&self.document_node(), &|node| {
+            node.attribute("id").map_or(false, |node_id| node_id == id)
+        })
+    }
+
+    #[inline]
+    fn node_kind(&self, node: &MemoryNode) -> Option<String> {
+        node.kind().map(|kind| kind.to_lowercase())
+    }
+    #[inline]
+    fn is_comment(&self, node: &MemoryNode) -> bool {
+        node.is_comment()
+    }
+    #[inline]
+    fn namespace_uri(&self, node: &MemoryNode) -> Option<String> {
+        node.namespace_uri()
+    }
+    #[inline]
+    fn text_content(&self, node: &MemoryNode) -> String {
+        node.text_content()
+    }
+    #[inline]
+    fn attribute(&self, node: &MemoryNode, key: &str) -> Option<String> {
+        node.attribute(key)
+    }
+    #[inline]
+    fn attribute_names(&self, node: &MemoryNode) -> Vec<String> {
+        node.attributes().into_iter().map(|(k, _)| k).collect()
+    }
+    #[inline]
+    fn outer_html(&self, node: &MemoryNode) -> String {
+        node.outer_html()
+    }
+
+    #[inline]
+    fn create_element(&self, kind: &str) -> MemoryNode {
+        MemoryNode::new_element(kind)
+    }
+    #[inline]
+    fn create_element_ns(&self, namespace: &str, kind: &str) -> MemoryNode {
+        MemoryNode::new_element_ns(namespace, kind)
+    }
+    #[inline]
+    fn create_text_node(&self, text: &str) -> MemoryNode {
+        MemoryNode::new_text(text)
+    }
+    #[inline]
+    fn attach_shadow(&self, host: &MemoryNode) -> Result<MemoryNode, String> {
+        Ok(host.attach_shadow())
+    }
+    #[inline]
+    fn set_inner_html(&self, node: &MemoryNode, html: &str) {
+        node.clear_children();
+
+        for child in parse_html(html) {
+            node.append_child(&child);
+        }
+    }
+    #[inline]
+    fn set_text_content(&self, node: &MemoryNode, text: &str) {
+        node.set_text_content(text);
+    }
+
+    #[inline]
+    fn parent_node(&self, node: &MemoryNode) -> Option<MemoryNode> {
+        node.parent()
+    }
+    #[inline]
+    fn child_nodes(&self, node: &MemoryNode) -> Vec<MemoryNode> {
+        node.children()
+    }
+    #[inline]
+    fn append_child(&self, parent: &MemoryNode, child: &MemoryNode) {
+        parent.append_child(child);
+    }
+    #[inline]
+    fn insert_before(&self, parent: &MemoryNode, child: &MemoryNode, reference: &MemoryNode) {
+        parent.insert_before(child, reference);
+    }
+    #[inline]
+    fn remove_child(&self, parent: &MemoryNode, child: &MemoryNode) {
+        parent.remove_child(child);
+    }
+    #[inline]
+    fn replace_child(&self, parent: &MemoryNode, new_child: &MemoryNode, old_child: &MemoryNode) {
+        parent.replace_child(new_child, old_child);
+    }
+
+    #[inline]
+    fn set_attribute(&self, node: &MemoryNode, key: &str, value: &str) {
+        if self.valid_attribute_name(key) {
+            node.set_attribute(key, value);
+        }
+    }
+    #[inline]
+    fn remove_attribute(&self, node: &MemoryNode, key: &str) {
+        node.remove_attribute(key);
+    }
+    #[inline]
+    fn set_attribute_ns(&self, node: &MemoryNode, _namespace: &str, key: &str, value: &str) {
+        if self.valid_attribute_name(key) {
+            node.set_attribute(key, value);
+        }
+    }
+    #[inline]
+    fn remove_attribute_ns(&self, node: &MemoryNode, namespace: &str, local_name: &str) {
+        for (key, _) in node.attributes() {
+            if attribute_local_name(&key) == local_name
+                && attribute_namespace(&key) == Some(namespace)
+            {
+                node.remove_attribute(&key);
+            }
+        }
+    }
+    #[inline]
+    fn property(&self, node: &MemoryNode, key: &str) -> Value {
+        node.property(key).unwrap_or(Value::Null)
+    }
+    #[inline]
+    fn set_property(&self, node: &MemoryNode, key: &str, value: &Value) {
+        node.set_property(key, value);
+    }
+    #[inline]
+    fn set_value(&self, node: &MemoryNode, value: &str) {
+        node.set_property("value", &Value::String(value.into()));
+    }
+    #[inline]
+    fn set_style(&self, node: &MemoryNode, name: &str, value: &str, important: bool) {
+        if important {
+            node.set_style(name, &format!("{} !important", value));
+        } else {
+            node.set_style(name, value);
+        }
+    }
+    #[inline]
+    fn remove_style(&self, node: &MemoryNode, name: &str) {
+        node.set_style(name, "");
+    }
+    #[inline]
+    fn add_class(&self, node: &MemoryNode, name: &str) {
+        node.add_class(name);
+    }
+    #[inline]
+    fn remove_class(&self, node: &MemoryNode, name: &str) {
+        node.remove_class(name);
+    }
+
+    #[inline]
+    fn focus(&self, node: &MemoryNode) {
+        self.0.borrow_mut().active_element = Some(node.clone());
+    }
+    #[inline]
+    fn blur(&self, node: &MemoryNode) {
+        let mut inner = self.0.borrow_mut();
+
+        if inner.active_element.as_ref() == Some(node) {
+            inner.active_element = None;
+        }
+    }
+    #[inline]
+    fn select(&self, node: &MemoryNode) {
+        let len = match node.property("value") {
+            Some(Value::String(ref value)) => value.chars().count(),
+            _ => 0,
+        };
+        node.set_property("selectionStart", &Value::Number(0.into()));
+        node.set_property("selectionEnd", &Value::Number(len.into()));
+    }
+    #[inline]
+    fn scroll_into_view(&self, _node: &MemoryNode) {}
+    #[inline]
+    fn click(&self, node: &MemoryNode) {
+        self.dispatch_event(node, "click", Prop::Null);
+    }
+    #[inline]
+    fn bounding_client_rect(&self, _node: &MemoryNode) -> ClientRect {
+        ClientRect::default()
+    }
+
+    #[inline]
+    fn add_event_listener(
+        &self,
+        target: &MemoryNode,
+        name: &str,
+        listener: DomListener<MemoryNode>,
+    ) -> usize {
+        let mut inner = self.0.borrow_mut();
+        let id = inner.next_listener_id;
+
+        inner.next_listener_id += 1;
+        inner.listeners.push(MemoryListener {
+            id: id,
+            target: target.clone(),
+            name: name.into(),
+            listener: Rc::new(listener),
+        });
+
+        id
+    }
+    #[inline]
+    fn remove_event_listener(&self, target: &MemoryNode, name: &str, listener: &usize) {
+        self.0
+            .borrow_mut()
+            .listeners
+            .retain(|l| !(l.id == *listener && &l.target == target && l.name == name));
+    }
+
+    #[inline]
+    fn request_animation_frame(&self, callback: DomFrameCallback) {
+        self.0.borrow_mut().frames.push(callback);
+    }
+    #[inline]
+    fn now(&self) -> f64 {
+        self.0.borrow().time
+    }
+
+    #[inline]
+    fn take_exception(&self) -> Option<String> {
+        self.0.borrow_mut().exception.take()
+    }
+
+    #[inline]
+    fn warn(&self, message: &str) {
+        self.0.borrow_mut().warnings.push(message.into());
+    }
+}
+
+/// the first node in document order, from `node` down, matching `predicate`
+#[inline]
+fn find_node(node: &MemoryNode, predicate: &Fn(&MemoryNode) -> bool) -> Option<MemoryNode> {
+    if predicate(node) {
+        Some(node.clone())
+    } else {
+        node.children()
+            .iter()
+            .filter_map(|child| find_node(child, predicate))
+            .next()
+    }
+}
+
+#[test]
+fn test_memory_document() {
+    let document = MemoryDocument::new();
+    let root = document.create_element("div");
+
+    document.append_child(&document.document_node(), &root);
+    document.set_inner_html(
+        &root,
+        "<ul class=\"List\"><li>a &amp; b</li><li style=\"color: red\">c<br></li></ul>",
+    );
+
+    let list = root.child(0).unwrap();
+    assert_eq!(list.kind(), Some("ul".to_string()));
+    assert_eq!(list.attribute("class"), Some("List".to_string()));
+    assert_eq!(list.children().len(), 2);
+    assert_eq!(list.child(0).unwrap().text_content(), "a & b");
+    assert_eq!(list.child(1).unwrap().style("color"), Some("red".to_string()));
+
+    let item = document.create_element("li");
+    document.set_text_content(&item, "<d>");
+    document.insert_before(&list, &item, &list.child(0).unwrap());
+    document.remove_child(&list, &list.child(2).unwrap());
+
+    assert_eq!(
+        root.inner_html(),
+        "<ul class=\"List\"><li>&lt;d&gt;</li><li>a &amp; b</li></ul>"
+    );
+}
@@ -0,0 +1,539 @@
+use std::fmt;
+use std::rc::{Rc, Weak};
+use std::cell::RefCell;
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+use serde_json::{Map, Value};
+
+use super::super::ToHtmlString;
+use super::super::namespace::HTML_NAMESPACE;
+
+static NEXT_ID: AtomicUsize = AtomicUsize::new(1);
+
+const VOID_ELEMENTS: [&'static str; 14] = [
+    "area", "base", "br", "col", "embed", "hr", "img", "input", "link", "meta", "param", "source",
+    "track", "wbr",
+];
+
+enum MemoryNodeKind {
+    Document,
+    ShadowRoot,
+    Element {
+        kind: String,
+        namespace: String,
+        attributes: Vec<(String, String)>,
+        properties: Map<String, Value>,
+        style: Vec<(String, String)>,
+    },
+    Text(String),
+    Comment(String),
+}
+
+struct MemoryNodeInner {
+    id: usize,
+    kind: MemoryNodeKind,
+    parent: Option<Weak<RefCell<MemoryNodeInner>>>,
+    children: Vec<MemoryNode>,
+    host: Option<Weak<RefCell<MemoryNodeInner>>>,
+    shadow_root: Option<MemoryNode>,
+}
+
+#[derive(Clone)]
+pub struct MemoryNode(Rc<RefCell<MemoryNodeInner>>);
+
+impl MemoryNode {
+    #[inline]
+    fn new(kind: MemoryNodeKind) -> Self {
+        MemoryNode(Rc::new(RefCell::new(MemoryNodeInner {
+            id: NEXT_ID.fetch_add(1, Ordering::SeqCst),
+            kind: kind,
+            parent: None,
+            children: Vec::new(),
+            host: None,
+            shadow_root: None,
+        })))
+    }
+
+    #[inline]
+    pub fn new_document() -> Self {
+        Self::new(MemoryNodeKind::Document)
+    }
+    #[inline]
+    pub fn new_element(kind: &str) -> Self {
+        Self::new_element_ns(HTML_NAMESPACE, kind)
+    }
+    #[inline]
+    pub fn new_element_ns(namespace: &str, kind: &str) -> Self {
+        Self::new(MemoryNodeKind::Element {
+            kind: if namespace == HTML_NAMESPACE {
+                kind.to_lowercase()
+            } else {
+                kind.into()
+            },
+            namespace: namespace.into(),
+            attributes: Vec::new(),
+            properties: Map::new(),
+            style: Vec::new(),
+        })
+    }
+    #[inline]
+    pub fn new_text(text: &str) -> Self {
+        Self::new(MemoryNodeKind::Text(text.into()))
+    }
+
+    #[inline]
+    pub fn new_comment(text: &str) -> Self {
+        Self::new(MemoryNodeKind::Comment(text.into()))
+    }
+
+    #[inline]
+    pub fn id(&self) -> usize {
+        self.0.borrow().id
+    }
+
+    #[inline]
+    pub fn is_document(&self) -> bool {
+        match &self.0.borrow().kind {
+            &MemoryNodeKind::Document => true,
+            _ => false,
+        }
+    }
+    #[inline]
+    pub fn is_shadow_root(&self) -> bool {
+        match &self.0.borrow().kind {
+            &MemoryNodeKind::ShadowRoot => true,
+            _ => false,
+        }
+    }
+    #[inline]
+    pub fn is_element(&self) -> bool {
+        match &self.0.borrow().kind {
+            &MemoryNodeKind::Element { .. } => true,
+            _ => false,
+        }
+    }
+    #[inline]
+    pub fn is_text(&self) -> bool {
+        match &self.0.borrow().kind {
+            &MemoryNodeKind::Text(_) => true,
+            _ => false,
+        }
+    }
+    #[inline]
+    pub fn is_comment(&self) -> bool {
+        match &self.0.borrow().kind {
+            &MemoryNodeKind::Comment(_) => true,
+            _ => false,
+        }
+    }
+
+    #[inline]
+    pub fn kind(&self) -> Option<String> {
+        match &self.0.borrow().kind {
+            &MemoryNodeKind::Element { ref kind, .. } => Some(kind.clone()),
+            _ => None,
+        }
+    }
+    #[inline]
+    pub fn namespace_uri(&self) -> Option<String> {
+        match &self.0.borrow().kind {
+            &MemoryNodeKind::Element { ref namespace, .. } => Some(namespace.clone()),
+            _ => None,
+        }
+    }
+    #[inline]
+    pub fn text(&self) -> Option<String> {
+        match &self.0.borrow().kind {
+            &MemoryNodeKind::Text(ref text) => Some(text.clone()),
+            _ => None,
+        }
+    }
+    #[inline]
+    pub fn text_content(&self) -> String {
+        match &self.0.borrow().kind {
+            &MemoryNodeKind::Text(ref text) | &MemoryNodeKind::Comment(ref text) => text.clone(),
+            _ => {
+                let mut out = String::new();
+
+                for child in &self.0.borrow().children {
+                    if !child.is_comment() {
+                        out.push_str(&child.text_content());
+                    }
+                }
+
+                out
+            }
+        }
+    }
+    #[inline]
+    pub fn set_text_content(&self, text: &str) {
+        let is_text = match &mut self.0.borrow_mut().kind {
+            &mut MemoryNodeKind::Text(ref mut data)
+            | &mut MemoryNodeKind::Comment(ref mut data) => {
+                *data = text.into();
+                true
+            }
+            _ => false,
+        };
+
+        if !is_text {
+            self.clear_children();
+
+            if !text.is_empty() {
+                self.append_child(&MemoryNode::new_text(text));
+            }
+        }
+    }
+
+    #[inline]
+    pub fn attribute(&self, key: &str) -> Option<String> {
+        match &self.0.borrow().kind {
+            &MemoryNodeKind::Element { ref attributes, .. } => attributes
+                .iter()
+                .find(|&&(ref k, _)| k == key)
+                .map(|&(_, ref v)| v.clone()),
+            _ => None,
+        }
+    }
+    #[inline]
+    pub fn attributes(&self) -> Vec<(String, String)> {
+        match &self.0.borrow().kind {
+            &MemoryNodeKind::Element { ref attributes, .. } => attributes.clone(),
+            _ => Vec::new(),
+        }
+    }
+    #[inline]
+    pub fn set_attribute(&self, key: &str, value: &str) {
+        if key == "style" {
+            self.clear_style();
+
+            for declaration in value.split(';') {
+                let mut parts = declaration.splitn(2, ':');
+
+                if let (Some(k), Some(v)) = (parts.next(), parts.next()) {
+                    self.set_style(k.trim(), v.trim());
+                }
+            }
+        }
+        self.set_raw_attribute(key, value);
+    }
+    #[inline]
+    pub fn remove_attribute(&self, key: &str) {
+        if key == "style" {
+            self.clear_style();
+        }
+        self.remove_raw_attribute(key);
+    }
+
+    /// adds `name` to the `class` attribute, like `classList.add`
+    #[inline]
+    pub fn add_class(&self, name: &str) {
+        let class = self.attribute("class").unwrap_or_default();
+
+        if !class.split_whitespace().any(|n| n == name) {
+            let mut names: Vec<&str> = class.split_whitespace().collect();
+            names.push(name);
+            self.set_raw_attribute("class", &names.join(" "));
+        }
+    }
+    #[inline]
+    pub fn remove_class(&self, name: &str) {
+        if let Some(class) = self.attribute("class") {
+            let names: Vec<&str> = class.split_whitespace().filter(|&n| n != name).collect();
+            self.set_raw_attribute("class", &names.join(" "));
+        }
+    }
+
+    #[inline]
+    fn set_raw_attribute(&self, key: &str, value: &str) {
+        if let &mut MemoryNodeKind::Element {
+            ref mut attributes, ..
+        } = &mut self.0.borrow_mut().kind
+        {
+            if let Some(entry) = attributes.iter_mut().find(|&&mut (ref k, _)| k == key) {
+                entry.1 = value.into();
+                return;
+            }
+            attributes.push((key.into(), value.into()));
+        }
+    }
+    #[inline]
+    fn remove_raw_attribute(&self, key: &str) {
+        if let &mut MemoryNodeKind::Element {
+            ref mut attributes, ..
+        } = &mut self.0.borrow_mut().kind
+        {
+            attributes.retain(|&(ref k, _)| k != key);
+        }
+    }
+
+    #[inline]
+    pub fn property(&self, key: &str) -> Option<Value> {
+        match &self.0.borrow().kind {
+            &MemoryNodeKind::Element { ref properties, .. } => properties.get(key).cloned(),
+            _ => None,
+        }
+    }
+    #[inline]
+    pub fn set_property(&self, key: &str, value: &Value) {
+        match key {
+            "id" => self.set_attribute("id", &value_to_string(value)),
+            "className" => self.set_attribute("class", &value_to_string(value)),
+            "textContent" => self.set_text_content(&value_to_string(value)),
+            _ => if let &mut MemoryNodeKind::Element {
+                ref mut properties, ..
+            } = &mut self.0.borrow_mut().kind
+            {
+                properties.insert(key.into(), value.clone());
+            },
+        }
+    }
+
+    #[inline]
+    pub fn style(&self, key: &str) -> Option<String> {
+        match &self.0.borrow().kind {
+            &MemoryNodeKind::Element { ref style, .. } => style
+                .iter()
+                .find(|&&(ref k, _)| k == key)
+                .map(|&(_, ref v)| v.clone()),
+            _ => None,
+        }
+    }
+    #[inline]
+    pub fn set_style(&self, key: &str, value: &str) {
+        if let &mut MemoryNodeKind::Element { ref mut style, .. } = &mut self.0.borrow_mut().kind {
+            if value.is_empty() {
+                style.retain(|&(ref k, _)| k != key);
+            } else if let Some(entry) = style.iter_mut().find(|&&mut (ref k, _)| k == key) {
+                entry.1 = value.into();
+            } else {
+                style.push((key.into(), value.into()));
+            }
+        }
+
+        let style = self.style_string();
+        if style.is_empty() {
+            self.remove_raw_attribute("style");
+        } else {
+            self.set_raw_attribute("style", &style);
+        }
+    }
+    #[inline]
+    fn clear_style(&self) {
+        if let &mut MemoryNodeKind::Element { ref mut style, .. } = &mut self.0.borrow_mut().kind {
+            style.clear();
+        }
+    }
+    #[inline]
+    fn style_string(&self) -> String {
+        let mut out = String::new();
+
+        if let &MemoryNodeKind::Element { ref style, .. } = &self.0.borrow().kind {
+            for &(ref k, ref v) in style {
+                if !out.is_empty() {
+                    out.push(' ');
+                }
+                out.push_str(k);
+                out.push_str(": ");
+                out.push_str(v);
+                out.push(';');
+            }
+        }
+
+        out
+    }
+
+    #[inline]
+    pub fn parent(&self) -> Option<MemoryNode> {
+        self.0
+            .borrow()
+            .parent
+            .as_ref()
+            .and_then(Weak::upgrade)
+            .map(MemoryNode)
+    }
+    /// the element a shadow root is attached to
+    #[inline]
+    pub fn host(&self) -> Option<MemoryNode> {
+        self.0
+            .borrow()
+            .host
+            .as_ref()
+            .and_then(Weak::upgrade)
+            .map(MemoryNode)
+    }
+    #[inline]
+    pub fn shadow_root(&self) -> Option<MemoryNode> {
+        self.0.borrow().shadow_root.clone()
+    }
+    /// the shadow root of this node, attaching one first if it has none, its
+    /// children are not part of this node's html
+    #[inline]
+    pub fn attach_shadow(&self) -> MemoryNode {
+        if let Some(shadow_root) = self.shadow_root() {
+            return shadow_root;
+        }
+        let shadow_root = Self::new(MemoryNodeKind::ShadowRoot);
+        shadow_root.0.borrow_mut().host = Some(Rc::downgrade(&self.0));
+        self.0.borrow_mut().shadow_root = Some(shadow_root.clone());
+        shadow_root
+    }
+
+    #[inline]
+    pub fn children(&self) -> Vec<MemoryNode> {
+        self.0.borrow().children.clone()
+    }
+    #[inline]
+    pub fn child(&self, index: usize) -> Option<MemoryNode> {
+        self.0.borrow().children.get(index).cloned()
+    }
+    #[inline]
+    pub fn index_of(&self, child: &MemoryNode) -> Option<usize> {
+        self.0.borrow().children.iter().position(|c| c == child)
+    }
+
+    #[inline]
+    pub fn append_child(&self, child: &MemoryNode) {
+        child.detach();
+        child.0.borrow_mut().parent = Some(Rc::downgrade(&self.0));
+        self.0.borrow_mut().children.push(child.clone());
+    }
+    #[inline]
+    pub fn insert_before(&self, child: &MemoryNode, reference: &MemoryNode) {
+        child.detach();
+
+        if let Some(index) = self.index_of(reference) {
+            child.0.borrow_mut().parent = Some(Rc::downgrade(&self.0));
+            self.0.borrow_mut().children.insert(index, child.clone());
+        } else {
+            self.append_child(child);
+        }
+    }
+    #[inline]
+    pub fn remove_child(&self, child: &MemoryNode) {
+        if let Some(index) = self.index_of(child) {
+            self.0.borrow_mut().children.remove(index);
+            child.0.borrow_mut().parent = None;
+        }
+    }
+    #[inline]
+    pub fn replace_child(&self, new_child: &MemoryNode, old_child: &MemoryNode) {
+        new_child.detach();
+
+        if let Some(index) = self.index_of(old_child) {
+            new_child.0.borrow_mut().parent = Some(Rc::downgrade(&self.0));
+            self.0.borrow_mut().children[index] = new_child.clone();
+            old_child.0.borrow_mut().parent = None;
+        }
+    }
+    #[inline]
+    pub fn clear_children(&self) {
+        let children = ::std::mem::replace(&mut self.0.borrow_mut().children, Vec::new());
+
+        for child in children {
+            child.0.borrow_mut().parent = None;
+        }
+    }
+    #[inline]
+    fn detach(&self) {
+        if let Some(parent) = self.parent() {
+            parent.remove_child(self);
+        }
+    }
+
+    #[inline]
+    pub fn inner_html(&self) -> String {
+        let mut out = String::new();
+
+        for child in &self.0.borrow().children {
+            out.push_str(&child.outer_html());
+        }
+
+        out
+    }
+    #[inline]
+    pub fn outer_html(&self) -> String {
+        match &self.0.borrow().kind {
+            &MemoryNodeKind::Document | &MemoryNodeKind::ShadowRoot => self.inner_html(),
+            &MemoryNodeKind::Text(ref text) => escape_text(text),
+            &MemoryNodeKind::Comment(ref text) => format!("<!--{}-->", text),
+            &MemoryNodeKind::Element {
+                ref kind,
+                ref namespace,
+                ref attributes,
+                ..
+            } => {
+                let mut out = String::new();
+
+                out.push('<');
+                out.push_str(kind);
+
+                for &(ref k, ref v) in attributes {
+                    push_attribute(&mut out, k, v);
+                }
+
+                out.push('>');
+
+                if !(namespace == HTML_NAMESPACE && is_void_element(kind)) {
+                    out.push_str(&self.inner_html());
+                    out.push_str("</");
+                    out.push_str(kind);
+                    out.push('>');
+                }
+
+                out
+            }
+        }
+    }
+}
+
+impl ToHtmlString for MemoryNode {
+    #[inline]
+    fn to_html_string(&self) -> String {
+        self.outer_html()
+    }
+}
+
+impl PartialEq for MemoryNode {
+    #[inline]
+    fn eq(&self, other: &MemoryNode) -> bool {
+        Rc::ptr_eq(&self.0, &other.0)
+    }
+}
+impl Eq for MemoryNode {}
+
+impl fmt::Debug for MemoryNode {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "MemoryNode({}, {})", self.id(), self.outer_html())
+    }
+}
+
+#[inline]
+pub fn is_void_element(kind: &str) -> bool {
+    VOID_ELEMENTS.contains(&kind)
+}
+
+#[inline]
+fn value_to_string(value: &Value) -> String {
+    match value {
+        &Value::Null => String::new(),
+        &Value::String(ref string) => string.clone(),
+        value => value.to_string(),
+    }
+}
+
+#[inline]
+fn push_attribute(out: &mut String, key: &str, value: &str) {
+    out.push(' ');
+    out.push_str(key);
+    out.push_str("=\"");
+    out.push_str(&value.replace('&', "&amp;").replace('"', "&quot;"));
+    out.push('"');
+}
+
+#[inline]
+fn escape_text(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+}
@@ -0,0 +1,7 @@
+mod memory_document;
+mod memory_node;
+mod parse_html;
+
+pub use self::memory_document::MemoryDocument;
+pub use self::memory_node::MemoryNode;
+pub use self::parse_html::parse_html;
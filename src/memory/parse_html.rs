@@ -0,0 +1,124 @@
+use super::super::namespace::Namespace;
+use super::memory_node::{is_void_element, MemoryNode};
+
+#[inline]
+pub fn parse_html(html: &str) -> Vec<MemoryNode> {
+    let root = MemoryNode::new_document();
+    let mut stack = vec![(root.clone(), Namespace::Html)];
+    let mut rest = html;
+
+    while !rest.is_empty() {
+        if rest.starts_with("<!--") {
+            let end = rest[4..].find("-->").map(|i| i + 4).unwrap_or(rest.len());
+            let comment = MemoryNode::new_comment(&rest[4..end]);
+
+            stack.last().unwrap().0.append_child(&comment);
+            rest = if end < rest.len() { &rest[end + 3..] } else { "" };
+        } else if rest.starts_with("</") {
+            let end = rest.find('>').unwrap_or(rest.len());
+            let kind = rest[2..end].trim().to_lowercase();
+
+            if let Some(index) = stack.iter().rposition(|&(ref node, _)| {
+                node.kind().map(|k| k.to_lowercase()).as_ref() == Some(&kind)
+            }) {
+                stack.truncate(index);
+            }
+            rest = if end < rest.len() { &rest[end + 1..] } else { "" };
+        } else if rest.starts_with('<') && rest[1..].starts_with(is_tag_start) {
+            let parent_namespace = stack.last().unwrap().1;
+            let (node, namespace, self_closing, next) = parse_tag(&rest[1..], parent_namespace);
+            let kind = node.kind().unwrap();
+
+            stack.last().unwrap().0.append_child(&node);
+
+            if !self_closing && !(namespace.is_html() && is_void_element(&kind)) {
+                stack.push((node, namespace.children(&kind)));
+            }
+            rest = next;
+        } else {
+            let end = rest[1..].find('<').map(|i| i + 1).unwrap_or(rest.len());
+            let text = decode_entities(&rest[..end]);
+
+            stack.last().unwrap().0.append_child(&MemoryNode::new_text(&text));
+            rest = &rest[end..];
+        }
+    }
+
+    let nodes = root.children();
+    root.clear_children();
+    nodes
+}
+
+#[inline]
+fn is_tag_start(c: char) -> bool {
+    c.is_ascii_alphabetic()
+}
+
+#[inline]
+fn parse_tag(input: &str, namespace: Namespace) -> (MemoryNode, Namespace, bool, &str) {
+    let name_end = input
+        .find(|c: char| c.is_whitespace() || c == '/' || c == '>')
+        .unwrap_or(input.len());
+    let name = &input[..name_end];
+    let namespace = namespace.element(&name.to_lowercase());
+    let node = MemoryNode::new_element_ns(namespace.uri(), name);
+    let mut rest = &input[name_end..];
+
+    loop {
+        rest = rest.trim_left();
+
+        if rest.is_empty() {
+            return (node, namespace, false, rest);
+        } else if rest.starts_with("/>") {
+            return (node, namespace, true, &rest[2..]);
+        } else if rest.starts_with('>') {
+            return (node, namespace, false, &rest[1..]);
+        } else if rest.starts_with('/') {
+            rest = &rest[1..];
+            continue;
+        }
+
+        let key_end = rest.find(|c: char| c.is_whitespace() || c == '=' || c == '>' || c == '/')
+            .unwrap_or(rest.len());
+        let key = if namespace.is_html() {
+            rest[..key_end].to_lowercase()
+        } else {
+            rest[..key_end].to_string()
+        };
+        rest = rest[key_end..].trim_left();
+
+        let value = if rest.starts_with('=') {
+            rest = rest[1..].trim_left();
+
+            if rest.starts_with('"') || rest.starts_with('\'') {
+                let quote = &rest[..1];
+                let end = rest[1..].find(quote).map(|i| i + 1).unwrap_or(rest.len());
+                let value = decode_entities(&rest[1..end]);
+                rest = if end < rest.len() { &rest[end + 1..] } else { "" };
+                value
+            } else {
+                let end = rest.find(|c: char| c.is_whitespace() || c == '>')
+                    .unwrap_or(rest.len());
+                let value = decode_entities(&rest[..end]);
+                rest = &rest[end..];
+                value
+            }
+        } else {
+            String::new()
+        };
+
+        if !key.is_empty() {
+            node.set_attribute(&key, &value);
+        }
+    }
+}
+
+#[inline]
+fn decode_entities(text: &str) -> String {
+    text.replace("&lt;", "<")
+        .replace("&gt;", ">")
+        .replace("&quot;", "\"")
+        .replace("&#39;", "'")
+        .replace("&nbsp;", "\u{a0}")
+        .replace("&amp;", "&")
+}
@@ -0,0 +1,102 @@
+pub const HTML_NAMESPACE: &'static str = "http://www.w3.org/1999/xhtml";
+pub const SVG_NAMESPACE: &'static str = "http://www.w3.org/2000/svg";
+pub const MATHML_NAMESPACE: &'static str = "http://www.w3.org/1998/Math/MathML";
+pub const XLINK_NAMESPACE: &'static str = "http://www.w3.org/1999/xlink";
+pub const XML_NAMESPACE: &'static str = "http://www.w3.org/XML/1998/namespace";
+pub const XMLNS_NAMESPACE: &'static str = "http://www.w3.org/2000/xmlns/";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Namespace {
+    Html,
+    Svg,
+    MathMl,
+}
+
+impl Default for Namespace {
+    #[inline(always)]
+    fn default() -> Self {
+        Namespace::Html
+    }
+}
+
+impl Namespace {
+    #[inline]
+    pub fn from_uri(uri: Option<&str>) -> Self {
+        match uri {
+            Some(SVG_NAMESPACE) => Namespace::Svg,
+            Some(MATHML_NAMESPACE) => Namespace::MathMl,
+            _ => Namespace::Html,
+        }
+    }
+
+    #[inline]
+    pub fn uri(&self) -> &'static str {
+        match self {
+            &Namespace::Html => HTML_NAMESPACE,
+            &Namespace::Svg => SVG_NAMESPACE,
+            &Namespace::MathMl => MATHML_NAMESPACE,
+        }
+    }
+
+    #[inline]
+    pub fn is_html(&self) -> bool {
+        self == &Namespace::Html
+    }
+
+    /// the namespace of an element of `kind` created inside this namespace,
+    /// `svg` and `math` elements start their own namespace wherever they are
+    #[inline]
+    pub fn element(&self, kind: &str) -> Self {
+        match kind {
+            "svg" => Namespace::Svg,
+            "math" => Namespace::MathMl,
+            _ => *self,
+        }
+    }
+
+    /// the namespace children of an element of `kind` in this namespace
+    /// inherit, svg's `foreignObject` switches back to html
+    #[inline]
+    pub fn children(&self, kind: &str) -> Self {
+        if self == &Namespace::Svg && kind.eq_ignore_ascii_case("foreignObject") {
+            Namespace::Html
+        } else {
+            *self
+        }
+    }
+}
+
+/// the namespace of a prefixed attribute name like `xlink:href`
+#[inline]
+pub fn attribute_namespace(key: &str) -> Option<&'static str> {
+    if key.starts_with("xlink:") {
+        Some(XLINK_NAMESPACE)
+    } else if key.starts_with("xml:") {
+        Some(XML_NAMESPACE)
+    } else if key == "xmlns" || key.starts_with("xmlns:") {
+        Some(XMLNS_NAMESPACE)
+    } else {
+        None
+    }
+}
+
+/// the local part of a possibly prefixed attribute name
+#[inline]
+pub fn attribute_local_name(key: &str) -> &str {
+    match key.find(':') {
+        Some(index) => &key[index + 1..],
+        None => key,
+    }
+}
+
+#[test]
+fn test_namespace() {
+    let svg = Namespace::Html.element("svg");
+    assert_eq!(svg, Namespace::Svg);
+    assert_eq!(svg.children("svg").element("circle"), Namespace::Svg);
+    assert_eq!(svg.children("foreignObject").element("div"), Namespace::Html);
+    assert_eq!(Namespace::from_uri(Some(MATHML_NAMESPACE)), Namespace::MathMl);
+    assert_eq!(attribute_namespace("xlink:href"), Some(XLINK_NAMESPACE));
+    assert_eq!(attribute_namespace("href"), None);
+    assert_eq!(attribute_local_name("xlink:href"), "href");
+}
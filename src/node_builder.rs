@@ -0,0 +1,136 @@
+use virtual_view::RawView;
+
+use super::{Dom, Namespace, NodeId, NodesIds, PatchReport, TextMode, ViewId};
+use super::controlled::restore_controlled;
+use super::portal::{portal_container, portal_target};
+use super::props::set_props;
+use super::raw_html::view_children;
+
+/// builds dom nodes straight from a `RawView` tree, so the created nodes are
+/// exactly the view's nodes, and registers each of them in `NodesIds`
+pub struct NodeBuilder<'a, D: 'a + Dom> {
+    document: &'a D,
+    nodes_ids: &'a NodesIds<D>,
+    text_mode: TextMode,
+}
+
+impl<'a, D: 'a + Dom> NodeBuilder<'a, D> {
+    #[inline(always)]
+    pub fn new(document: &'a D, nodes_ids: &'a NodesIds<D>, text_mode: TextMode) -> Self {
+        NodeBuilder {
+            document: document,
+            nodes_ids: nodes_ids,
+            text_mode: text_mode,
+        }
+    }
+
+    /// builds `view` as a child of an element whose children are in `namespace`,
+    /// text views are text nodes unless `TextMode::Span` wraps them in html
+    #[inline]
+    pub fn build(
+        &self,
+        id: ViewId,
+        view: &RawView,
+        namespace: Namespace,
+        report: &mut PatchReport,
+    ) -> D::Node {
+        let (handle, node) = self.create(id, view, namespace, report);
+
+        if let &RawView::Data { ref kind, .. } = view {
+            let children_namespace = namespace.element(kind).children(kind);
+
+            for (index, child) in view_children(view).iter().enumerate() {
+                let child_id = ViewId::Child(handle, child.key(), index);
+                let child_node = self.build(child_id, child, children_namespace, report);
+                self.document.append_child(&node, &self.anchor(child, &child_node));
+            }
+            self.restore_controlled(&node);
+        }
+
+        node
+    }
+
+    /// creates the node for `view` alone, with its props but without children,
+    /// and the handle it is registered with
+    #[inline]
+    pub fn create(
+        &self,
+        id: ViewId,
+        view: &RawView,
+        namespace: Namespace,
+        report: &mut PatchReport,
+    ) -> (NodeId, D::Node) {
+        let node = match view {
+            &RawView::Text(ref text) => if namespace.is_html() && self.text_mode == TextMode::Span {
+                let node = self.document.create_element("span");
+                self.document.set_text_content(&node, text);
+                node
+            } else {
+                self.document.create_text_node(text)
+            },
+            &RawView::Data {
+                ref kind,
+                ref props,
+                ..
+            } => {
+                let namespace = namespace.element(kind);
+                let node = if namespace.is_html() {
+                    self.document.create_element(kind)
+                } else {
+                    self.document.create_element_ns(namespace.uri(), kind)
+                };
+                set_props(self.document, self.nodes_ids, &node, props, report);
+                node
+            }
+        };
+
+        let handle = self.nodes_ids.insert_view_id(id, node.clone());
+        report.created += 1;
+        (handle, node)
+    }
+
+    /// the node to put in the parent for `node`, built from `view`, when
+    /// `view` is a portal `node` goes in the portal's container and an empty
+    /// text node holds its place in the parent
+    #[inline]
+    pub fn anchor(&self, view: &RawView, node: &D::Node) -> D::Node {
+        let target = match portal_target(view) {
+            Some(target) => target,
+            None => return node.clone(),
+        };
+
+        match portal_container(self.document, target) {
+            Some(container) => {
+                let placeholder = self.document.create_text_node("");
+                self.document.append_child(&container, node);
+                self.nodes_ids
+                    .insert_portal(placeholder.clone(), node.clone(), &container);
+                placeholder
+            }
+            None => {
+                let message = format!("no container {:?} for portal, mounted in place", target);
+                self.document.warn(&message);
+                node.clone()
+            }
+        }
+    }
+
+    /// sets the controlled props of `node` again, a `select`'s value only
+    /// sticks once the matching option is in it
+    #[inline]
+    pub fn restore_controlled(&self, node: &D::Node) -> usize {
+        restore_controlled(self.document, self.nodes_ids, node)
+    }
+
+    /// the namespace children created inside `parent` belong to
+    #[inline]
+    pub fn children_namespace(&self, parent: &D::Node) -> Namespace {
+        let namespace_uri = self.document.namespace_uri(parent);
+        let namespace = Namespace::from_uri(namespace_uri.as_ref().map(String::as_str));
+
+        match self.document.node_kind(parent) {
+            Some(ref kind) => namespace.children(kind),
+            None => namespace,
+        }
+    }
+}
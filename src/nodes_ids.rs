@@ -1,97 +1,816 @@
 use std::rc::Rc;
 use std::cell::RefCell;
 
-use fnv::FnvHashMap;
-use stdweb::web::Node;
+use fnv::{FnvHashMap, FnvHashSet};
+use serde_json::{Map, Value};
+use stdweb::web::Document;
 
-use super::NodeRef;
+use super::{Dom, RefChange, RefChangeKind};
 
-pub struct NodesIdsInner {
-    nodes: FnvHashMap<String, Node>,
-    ids: FnvHashMap<NodeRef, String>,
+/// a compact handle for a view id in `NodesIds`, nodes map to handles so
+/// finding the view of a node, like an event's target, copies no string
+/// until the id reaches the `EventManager`, handles of forgotten ids are
+/// reused once no child id hangs off them
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct NodeId(u32);
+
+impl NodeId {
+    #[inline]
+    pub fn index(&self) -> usize {
+        self.0 as usize
+    }
+}
+
+/// the view id to register a node for, a child's is its parent's handle and
+/// its key or index, so registering it builds no string
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ViewId<'a> {
+    Id(&'a str),
+    Child(NodeId, Option<&'a String>, usize),
+}
+
+/// the last part of a view id, a whole id for one without a known parent
+enum Segment {
+    Root(String),
+    Key(String),
+    Index(usize),
 }
 
-impl NodesIdsInner {
+struct Entry<N> {
+    parent: Option<NodeId>,
+    segment: Segment,
+    /// none once forgotten while child ids still hang off it
+    node: Option<N>,
+    children: usize,
+}
+
+pub struct NodesIdsInner<D: Dom = Document> {
+    entries: Vec<Option<Entry<D::Node>>>,
+    free: Vec<NodeId>,
+    roots: FnvHashMap<String, NodeId>,
+    indexed: FnvHashMap<(NodeId, usize), NodeId>,
+    keyed: FnvHashMap<NodeId, FnvHashMap<String, NodeId>>,
+    ids: FnvHashMap<D::NodeKey, NodeId>,
+    portals: FnvHashMap<D::NodeKey, (D::Node, D::Node)>,
+    placeholders: FnvHashMap<D::NodeKey, D::Node>,
+    containers: Vec<(D::Node, usize)>,
+    controlled: FnvHashMap<D::NodeKey, Map<String, Value>>,
+    opaque: FnvHashSet<D::NodeKey>,
+    refs: FnvHashMap<String, D::Node>,
+    ref_changes: Vec<(String, RefChangeKind, D::Node, Option<String>)>,
+}
+
+impl<D: Dom> NodesIdsInner<D> {
     #[inline]
     pub fn new() -> Self {
         NodesIdsInner {
-            nodes: FnvHashMap::default(),
+            entries: Vec::new(),
+            free: Vec::new(),
+            roots: FnvHashMap::default(),
+            indexed: FnvHashMap::default(),
+            keyed: FnvHashMap::default(),
             ids: FnvHashMap::default(),
+            portals: FnvHashMap::default(),
+            placeholders: FnvHashMap::default(),
+            containers: Vec::new(),
+            controlled: FnvHashMap::default(),
+            opaque: FnvHashSet::default(),
+            refs: FnvHashMap::default(),
+            ref_changes: Vec::new(),
+        }
+    }
+
+    /// registers `node` for `id`, keeping `id`'s handle if it has one, an id
+    /// under a known one is registered as its child
+    #[inline]
+    pub fn insert(&mut self, id: &str, node: D::Node) -> NodeId {
+        let handle = match self.find_handle(id) {
+            Some(handle) => handle,
+            None => {
+                let parent = id.rfind('.').and_then(|position| {
+                    self.find_handle(&id[..position])
+                        .map(|parent| (parent, &id[position + 1..]))
+                });
+
+                match parent {
+                    Some((parent, segment)) => {
+                        self.new_handle(Some(parent), child_segment(Some(segment), 0))
+                    }
+                    None => self.new_handle(None, Segment::Root(id.into())),
+                }
+            }
+        };
+        self.set_node(handle, node)
+    }
+    /// registers `node` for the child at `index` of `parent`, or with `key`
+    #[inline]
+    pub fn insert_child(
+        &mut self,
+        parent: NodeId,
+        key: Option<&String>,
+        index: usize,
+        node: D::Node,
+    ) -> NodeId {
+        let handle = match self.child_handle(parent, key.map(String::as_str), index) {
+            Some(handle) => handle,
+            None => {
+                let segment = child_segment(key.map(String::as_str), index);
+                self.new_handle(Some(parent), segment)
+            }
+        };
+        self.set_node(handle, node)
+    }
+    #[inline]
+    pub fn insert_view_id(&mut self, id: ViewId, node: D::Node) -> NodeId {
+        match id {
+            ViewId::Id(id) => self.insert(id, node),
+            ViewId::Child(parent, key, index) => self.insert_child(parent, key, index, node),
+        }
+    }
+    #[inline]
+    fn new_handle(&mut self, parent: Option<NodeId>, segment: Segment) -> NodeId {
+        let handle = match self.free.pop() {
+            Some(handle) => handle,
+            None => {
+                self.entries.push(None);
+                NodeId((self.entries.len() - 1) as u32)
+            }
+        };
+
+        match (&segment, parent) {
+            (&Segment::Root(ref id), _) => {
+                self.roots.insert(id.clone(), handle);
+            }
+            (&Segment::Index(index), Some(parent)) => {
+                self.indexed.insert((parent, index), handle);
+            }
+            (&Segment::Key(ref key), Some(parent)) => {
+                self.keyed
+                    .entry(parent)
+                    .or_insert_with(FnvHashMap::default)
+                    .insert(key.clone(), handle);
+            }
+            _ => (),
+        }
+        if let Some(parent) = parent {
+            if let Some(&mut Some(ref mut entry)) = self.entries.get_mut(parent.index()) {
+                entry.children += 1;
+            }
+        }
+        self.entries[handle.index()] = Some(Entry {
+            parent: parent,
+            segment: segment,
+            node: None,
+            children: 0,
+        });
+
+        handle
+    }
+    #[inline]
+    fn set_node(&mut self, handle: NodeId, node: D::Node) -> NodeId {
+        let node_ref = D::node_key(&node);
+        let old_node = match self.entries[handle.index()] {
+            Some(ref mut entry) => entry.node.take(),
+            None => None,
+        };
+
+        if let Some(old_node) = old_node {
+            let old_node_ref = D::node_key(&old_node);
+
+            if self.ids.get(&old_node_ref) == Some(&handle) {
+                self.ids.remove(&old_node_ref);
+            }
+            if old_node_ref != node_ref {
+                self.remove_node_refs(&old_node, handle);
+            }
+        }
+        if let Some(ref mut entry) = self.entries[handle.index()] {
+            entry.node = Some(node);
+        }
+        self.ids.insert(node_ref, handle);
+
+        handle
+    }
+
+    #[inline]
+    pub fn remove_node(&mut self, node: &D::Node) -> Option<String> {
+        let handle_option = self.ids.get(&D::node_key(node)).cloned();
+
+        handle_option.and_then(|handle| {
+            let id = self.handle_id(handle);
+            self.remove_handle(handle).and(id)
+        })
+    }
+    #[inline]
+    pub fn remove_id(&mut self, id: &str) -> Option<D::Node> {
+        let handle_option = self.id_handle(id);
+        handle_option.and_then(|handle| self.remove_handle(handle))
+    }
+    /// forgets `handle`, which may be given to another id afterwards
+    #[inline]
+    pub fn remove_handle(&mut self, handle: NodeId) -> Option<D::Node> {
+        let node = match self.entries.get_mut(handle.index()) {
+            Some(&mut Some(ref mut entry)) => entry.node.take(),
+            _ => None,
+        };
+
+        if let Some(ref node) = node {
+            let node_ref = D::node_key(node);
+
+            if self.ids.get(&node_ref) == Some(&handle) {
+                self.ids.remove(&node_ref);
+                self.controlled.remove(&node_ref);
+                self.opaque.remove(&node_ref);
+            }
+            self.remove_node_refs(node, handle);
+            self.release(handle);
         }
+        node
     }
+    /// frees a forgotten `handle` once no child id hangs off it, and then
+    /// its parent if it was the last one
+    #[inline]
+    fn release(&mut self, handle: NodeId) {
+        let parent = match self.entries[handle.index()] {
+            Some(ref entry) if entry.node.is_none() && entry.children == 0 => entry.parent,
+            _ => return,
+        };
+        let entry = self.entries[handle.index()].take().unwrap();
 
+        match (entry.segment, parent) {
+            (Segment::Root(id), _) => {
+                self.roots.remove(&id);
+            }
+            (Segment::Index(index), Some(parent)) => {
+                self.indexed.remove(&(parent, index));
+            }
+            (Segment::Key(key), Some(parent)) => {
+                let empty = match self.keyed.get_mut(&parent) {
+                    Some(keyed) => {
+                        keyed.remove(&key);
+                        keyed.is_empty()
+                    }
+                    None => false,
+                };
+                if empty {
+                    self.keyed.remove(&parent);
+                }
+            }
+            _ => (),
+        }
+        self.free.push(handle);
+
+        if let Some(parent) = parent {
+            if let Some(&mut Some(ref mut entry)) = self.entries.get_mut(parent.index()) {
+                entry.children -= 1;
+            }
+            self.release(parent);
+        }
+    }
+
+    #[inline]
+    pub fn node(&self, id: &str) -> Option<&D::Node> {
+        self.id_handle(id)
+            .and_then(|handle| self.handle_node(handle))
+    }
     #[inline]
-    pub fn insert(&mut self, id: String, node: Node) {
-        let node_ref: NodeRef = node.as_ref().into();
-        self.nodes.insert(id.clone(), node);
-        self.ids.insert(node_ref, id);
+    pub fn id(&self, node: &D::Node) -> Option<String> {
+        self.handle(node).and_then(|handle| self.handle_id(handle))
     }
 
     #[inline]
-    pub fn remove_node(&mut self, node: &Node) -> Option<String> {
-        let node_ref: NodeRef = node.as_ref().into();
-        let id_option = self.ids.remove(&node_ref);
+    pub fn handle(&self, node: &D::Node) -> Option<NodeId> {
+        self.ids.get(&D::node_key(node)).cloned()
+    }
+    #[inline]
+    pub fn id_handle(&self, id: &str) -> Option<NodeId> {
+        self.find_handle(id)
+            .and_then(|handle| self.handle_node(handle).map(|_| handle))
+    }
+    #[inline]
+    pub fn child_handle(&self, parent: NodeId, key: Option<&str>, index: usize) -> Option<NodeId> {
+        match key {
+            Some(key) => match index_segment(key) {
+                Some(index) => self.indexed.get(&(parent, index)).cloned(),
+                None => self.keyed
+                    .get(&parent)
+                    .and_then(|keyed| keyed.get(key))
+                    .cloned(),
+            },
+            None => self.indexed.get(&(parent, index)).cloned(),
+        }
+    }
+    /// the handle of `id`, forgotten or not, found from its root down a part
+    /// at a time, a part with no child may be a key holding dots, so it takes
+    /// in the next one
+    #[inline]
+    fn find_handle(&self, id: &str) -> Option<NodeId> {
+        if let Some(&handle) = self.roots.get(id) {
+            return Some(handle);
+        }
+        let ends = id.match_indices('.')
+            .map(|(position, _)| position)
+            .chain(Some(id.len()));
+        let mut handle = None;
+        let mut start = 0;
+
+        for end in ends {
+            let found = match handle {
+                Some(parent) => self.child_handle(parent, Some(&id[start..end]), 0),
+                None => self.roots.get(&id[..end]).cloned(),
+            };
+            if found.is_some() {
+                handle = found;
+                start = end + 1;
+            }
+        }
 
-        if let Some(id) = id_option {
-            self.nodes.remove(&id);
-            Some(id)
+        if start > id.len() {
+            handle
         } else {
             None
         }
     }
     #[inline]
-    pub fn remove_id(&mut self, id: &str) -> Option<Node> {
-        let node_option = self.nodes.remove(id);
+    pub fn handle_node(&self, handle: NodeId) -> Option<&D::Node> {
+        match self.entries.get(handle.index()) {
+            Some(&Some(ref entry)) => entry.node.as_ref(),
+            _ => None,
+        }
+    }
+    /// the string form of `handle`'s view id, built from its parents' parts
+    #[inline]
+    pub fn handle_id(&self, handle: NodeId) -> Option<String> {
+        self.handle_node(handle).map(|_| {
+            let mut id = String::new();
+            self.push_id(handle, &mut id);
+            id
+        })
+    }
+    /// the string form of `id`, only needed where a string id is reported
+    #[inline]
+    pub fn view_id_string(&self, id: ViewId) -> String {
+        match id {
+            ViewId::Id(id) => id.into(),
+            ViewId::Child(parent, key, index) => {
+                let mut id = String::new();
+                self.push_id(parent, &mut id);
+                id.push('.');
+                match key {
+                    Some(key) => id.push_str(key),
+                    None => id.push_str(&index.to_string()),
+                }
+                id
+            }
+        }
+    }
+    #[inline]
+    fn push_id(&self, handle: NodeId, id: &mut String) {
+        if let Some(&Some(ref entry)) = self.entries.get(handle.index()) {
+            if let Some(parent) = entry.parent {
+                self.push_id(parent, id);
+                id.push('.');
+            }
+            match entry.segment {
+                Segment::Root(ref segment) | Segment::Key(ref segment) => id.push_str(segment),
+                Segment::Index(index) => id.push_str(&index.to_string()),
+            }
+        }
+    }
+
+    #[inline]
+    pub fn insert_portal(&mut self, placeholder: D::Node, node: D::Node, container: &D::Node) {
+        self.remove_portal(&placeholder);
+
+        match self.container_index(container) {
+            Some(index) => self.containers[index].1 += 1,
+            None => self.containers.push((container.clone(), 1)),
+        }
+        self.placeholders.insert(D::node_key(&node), placeholder.clone());
+        self.portals
+            .insert(D::node_key(&placeholder), (node, container.clone()));
+    }
+    /// forgets the portal of `placeholder`, and its container once no other
+    /// portal is mounted into it
+    #[inline]
+    pub fn remove_portal(&mut self, placeholder: &D::Node) -> Option<D::Node> {
+        let (node, container) = match self.portals.remove(&D::node_key(placeholder)) {
+            Some(portal) => portal,
+            None => return None,
+        };
+
+        self.placeholders.remove(&D::node_key(&node));
+
+        if let Some(index) = self.container_index(&container) {
+            self.containers[index].1 -= 1;
+
+            if self.containers[index].1 == 0 {
+                self.containers.remove(index);
+            }
+        }
+        Some(node)
+    }
+    #[inline]
+    fn container_index(&self, container: &D::Node) -> Option<usize> {
+        let key = D::node_key(container);
+        self.containers
+            .iter()
+            .position(|&(ref c, _)| D::node_key(c) == key)
+    }
+
+    #[inline]
+    pub fn has_portals(&self) -> bool {
+        !self.portals.is_empty()
+    }
+    /// the nodes portals are mounted into, each once
+    #[inline]
+    pub fn portal_containers(&self) -> Vec<D::Node> {
+        self.containers
+            .iter()
+            .map(|&(ref container, _)| container.clone())
+            .collect()
+    }
+    /// the portal node `placeholder` stands in for
+    #[inline]
+    pub fn portal(&self, placeholder: &D::Node) -> Option<&D::Node> {
+        self.portals
+            .get(&D::node_key(placeholder))
+            .map(|&(ref node, _)| node)
+    }
+    /// the node standing in for the portal `node` in its parent
+    #[inline]
+    pub fn placeholder(&self, node: &D::Node) -> Option<&D::Node> {
+        self.placeholders.get(&D::node_key(node))
+    }
+
+    #[inline]
+    pub fn set_controlled(&mut self, node: &D::Node, key: &str, value: Value) {
+        self.controlled
+            .entry(D::node_key(node))
+            .or_insert_with(Map::new)
+            .insert(key.into(), value);
+    }
+    #[inline]
+    pub fn remove_controlled(&mut self, node: &D::Node, key: &str) {
+        let node_ref = D::node_key(node);
+        let empty = match self.controlled.get_mut(&node_ref) {
+            Some(props) => {
+                props.remove(key);
+                props.is_empty()
+            }
+            None => false,
+        };
+
+        if empty {
+            self.controlled.remove(&node_ref);
+        }
+    }
+    /// the controlled form props of `node` and the values they are kept at
+    #[inline]
+    pub fn controlled(&self, node: &D::Node) -> Option<&Map<String, Value>> {
+        self.controlled.get(&D::node_key(node))
+    }
+    #[inline]
+    pub fn has_controlled(&self) -> bool {
+        !self.controlled.is_empty()
+    }
 
-        if let Some(node) = node_option {
-            let node_ref: NodeRef = node.as_ref().into();
-            self.ids.remove(&node_ref);
-            Some(node)
+    /// marks `node` as holding raw html, its children are not the view's
+    #[inline]
+    pub fn set_opaque(&mut self, node: &D::Node, opaque: bool) {
+        if opaque {
+            self.opaque.insert(D::node_key(node));
         } else {
-            None
+            self.opaque.remove(&D::node_key(node));
         }
     }
+    #[inline]
+    pub fn is_opaque(&self, node: &D::Node) -> bool {
+        self.opaque.contains(&D::node_key(node))
+    }
+
+    /// gives `node` the ref `name`, taking it from any other node
+    #[inline]
+    pub fn set_ref(&mut self, name: &str, node: &D::Node) {
+        let kind = match self.refs.insert(name.into(), node.clone()) {
+            Some(ref old_node) if D::node_key(old_node) == D::node_key(node) => return,
+            Some(_) => RefChangeKind::Replaced,
+            None => RefChangeKind::Mounted,
+        };
+        self.ref_changes.push((name.into(), kind, node.clone(), None));
+    }
+    #[inline]
+    pub fn remove_ref(&mut self, name: &str) {
+        if let Some(node) = self.refs.remove(name) {
+            let id = self.id(&node);
+            self.ref_changes
+                .push((name.into(), RefChangeKind::Removed, node, id));
+        }
+    }
+    #[inline]
+    pub fn ref_node(&self, name: &str) -> Option<&D::Node> {
+        self.refs.get(name)
+    }
+    /// the name of a ref `node` has, if it has any
+    #[inline]
+    pub fn node_ref(&self, node: &D::Node) -> Option<&String> {
+        let node_ref = D::node_key(node);
 
+        self.refs
+            .iter()
+            .find(|&(_, ref_node)| D::node_key(ref_node) == node_ref)
+            .map(|(name, _)| name)
+    }
+    /// the ref changes since the last call, changes to nodes that never got
+    /// an id are dropped
     #[inline]
-    pub fn node(&self, id: &str) -> Option<&Node> {
-        self.nodes.get(id)
+    pub fn take_ref_changes(&mut self) -> Vec<RefChange> {
+        let ref_changes: Vec<_> = self.ref_changes.drain(..).collect();
+
+        ref_changes
+            .into_iter()
+            .filter_map(|(name, kind, node, id)| {
+                id.or_else(|| self.id(&node))
+                    .map(|id| RefChange {
+                        name: name,
+                        id: id,
+                        kind: kind,
+                    })
+            })
+            .collect()
     }
+
+    /// removes the refs of `node`, which had the view id of `handle`
     #[inline]
-    pub fn id(&self, node: &Node) -> Option<&String> {
-        let node_ref: NodeRef = node.as_ref().into();
-        self.ids.get(&node_ref)
+    fn remove_node_refs(&mut self, node: &D::Node, handle: NodeId) {
+        let node_ref = D::node_key(node);
+        let names: Vec<String> = self.refs
+            .iter()
+            .filter(|&(_, ref_node)| D::node_key(ref_node) == node_ref)
+            .map(|(name, _)| name.clone())
+            .collect();
+
+        if names.is_empty() {
+            return;
+        }
+        let mut id = String::new();
+        self.push_id(handle, &mut id);
+
+        for name in names {
+            self.refs.remove(&name);
+            self.ref_changes
+                .push((name, RefChangeKind::Removed, node.clone(), Some(id.clone())));
+        }
     }
 }
 
-#[derive(Clone)]
-pub struct NodesIds(Rc<RefCell<NodesIdsInner>>);
+pub struct NodesIds<D: Dom = Document>(Rc<RefCell<NodesIdsInner<D>>>);
+
+impl<D: Dom> Clone for NodesIds<D> {
+    #[inline]
+    fn clone(&self) -> Self {
+        NodesIds(self.0.clone())
+    }
+}
 
-impl NodesIds {
+impl<D: Dom> NodesIds<D> {
     #[inline]
     pub fn new() -> Self {
         NodesIds(Rc::new(RefCell::new(NodesIdsInner::new())))
     }
 
     #[inline]
-    pub fn insert(&self, id: String, node: Node) {
-        self.0.borrow_mut().insert(id, node);
+    pub fn insert(&self, id: &str, node: D::Node) -> NodeId {
+        self.0.borrow_mut().insert(id, node)
+    }
+    #[inline]
+    pub fn insert_child(
+        &self,
+        parent: NodeId,
+        key: Option<&String>,
+        index: usize,
+        node: D::Node,
+    ) -> NodeId {
+        self.0.borrow_mut().insert_child(parent, key, index, node)
+    }
+    #[inline]
+    pub fn insert_view_id(&self, id: ViewId, node: D::Node) -> NodeId {
+        self.0.borrow_mut().insert_view_id(id, node)
     }
 
     #[inline]
-    pub fn remove_node(&mut self, node: &Node) -> Option<String> {
+    pub fn remove_node(&self, node: &D::Node) -> Option<String> {
         self.0.borrow_mut().remove_node(node)
     }
     #[inline]
-    pub fn remove_id(&mut self, id: &str) -> Option<Node> {
+    pub fn remove_id(&self, id: &str) -> Option<D::Node> {
         self.0.borrow_mut().remove_id(id)
     }
+    #[inline]
+    pub fn remove_handle(&self, handle: NodeId) -> Option<D::Node> {
+        self.0.borrow_mut().remove_handle(handle)
+    }
 
     #[inline]
-    pub fn node(&self, id: &str) -> Option<Node> {
+    pub fn node(&self, id: &str) -> Option<D::Node> {
         self.0.borrow().node(id).map(Clone::clone)
     }
     #[inline]
-    pub fn id(&self, node: &Node) -> Option<String> {
-        self.0.borrow().id(node).map(Clone::clone)
+    pub fn id(&self, node: &D::Node) -> Option<String> {
+        self.0.borrow().id(node)
+    }
+
+    #[inline]
+    pub fn handle(&self, node: &D::Node) -> Option<NodeId> {
+        self.0.borrow().handle(node)
+    }
+    #[inline]
+    pub fn id_handle(&self, id: &str) -> Option<NodeId> {
+        self.0.borrow().id_handle(id)
+    }
+    #[inline]
+    pub fn handle_node(&self, handle: NodeId) -> Option<D::Node> {
+        self.0.borrow().handle_node(handle).map(Clone::clone)
+    }
+    #[inline]
+    pub fn child_handle(&self, parent: NodeId, key: Option<&str>, index: usize) -> Option<NodeId> {
+        self.0.borrow().child_handle(parent, key, index)
+    }
+    #[inline]
+    pub fn handle_id(&self, handle: NodeId) -> Option<String> {
+        self.0.borrow().handle_id(handle)
+    }
+    #[inline]
+    pub fn view_id_string(&self, id: ViewId) -> String {
+        self.0.borrow().view_id_string(id)
+    }
+
+    #[inline]
+    pub fn insert_portal(&self, placeholder: D::Node, node: D::Node, container: &D::Node) {
+        self.0.borrow_mut().insert_portal(placeholder, node, container);
+    }
+    #[inline]
+    pub fn remove_portal(&self, placeholder: &D::Node) -> Option<D::Node> {
+        self.0.borrow_mut().remove_portal(placeholder)
+    }
+
+    #[inline]
+    pub fn has_portals(&self) -> bool {
+        self.0.borrow().has_portals()
+    }
+    #[inline]
+    pub fn portal_containers(&self) -> Vec<D::Node> {
+        self.0.borrow().portal_containers()
+    }
+    #[inline]
+    pub fn portal(&self, placeholder: &D::Node) -> Option<D::Node> {
+        self.0.borrow().portal(placeholder).map(Clone::clone)
+    }
+    #[inline]
+    pub fn placeholder(&self, node: &D::Node) -> Option<D::Node> {
+        self.0.borrow().placeholder(node).map(Clone::clone)
+    }
+
+    #[inline]
+    pub fn set_controlled(&self, node: &D::Node, key: &str, value: Value) {
+        self.0.borrow_mut().set_controlled(node, key, value);
+    }
+    #[inline]
+    pub fn remove_controlled(&self, node: &D::Node, key: &str) {
+        self.0.borrow_mut().remove_controlled(node, key);
+    }
+    #[inline]
+    pub fn controlled(&self, node: &D::Node) -> Option<Map<String, Value>> {
+        self.0.borrow().controlled(node).map(Clone::clone)
+    }
+    #[inline]
+    pub fn has_controlled(&self) -> bool {
+        self.0.borrow().has_controlled()
+    }
+
+    #[inline]
+    pub fn set_opaque(&self, node: &D::Node, opaque: bool) {
+        self.0.borrow_mut().set_opaque(node, opaque);
+    }
+    #[inline]
+    pub fn is_opaque(&self, node: &D::Node) -> bool {
+        self.0.borrow().is_opaque(node)
+    }
+
+    #[inline]
+    pub fn set_ref(&self, name: &str, node: &D::Node) {
+        self.0.borrow_mut().set_ref(name, node);
+    }
+    #[inline]
+    pub fn remove_ref(&self, name: &str) {
+        self.0.borrow_mut().remove_ref(name);
+    }
+    #[inline]
+    pub fn ref_node(&self, name: &str) -> Option<D::Node> {
+        self.0.borrow().ref_node(name).map(Clone::clone)
+    }
+    #[inline]
+    pub fn node_ref(&self, node: &D::Node) -> Option<String> {
+        self.0.borrow().node_ref(node).map(Clone::clone)
+    }
+    #[inline]
+    pub fn take_ref_changes(&self) -> Vec<RefChange> {
+        self.0.borrow_mut().take_ref_changes()
+    }
+
+    /// the node that sits in the parent for `node`, its placeholder if it is
+    /// a portal
+    #[inline]
+    pub fn anchor(&self, node: &D::Node) -> D::Node {
+        self.placeholder(node).unwrap_or_else(|| node.clone())
+    }
+}
+
+/// the segment of a child, a key that reads as an index is one, as both
+/// give the same string id
+#[inline]
+fn child_segment(key: Option<&str>, index: usize) -> Segment {
+    match key {
+        Some(key) => match index_segment(key) {
+            Some(index) => Segment::Index(index),
+            None => Segment::Key(key.into()),
+        },
+        None => Segment::Index(index),
+    }
+}
+
+#[inline]
+fn index_segment(segment: &str) -> Option<usize> {
+    let canonical = !segment.is_empty() && (segment == "0" || !segment.starts_with('0'))
+        && segment.bytes().all(|byte| byte.is_ascii_digit());
+
+    if canonical {
+        segment.parse().ok()
+    } else {
+        None
     }
 }
+
+#[test]
+fn test_nodes_ids_handles() {
+    use super::MemoryDocument;
+
+    let document = MemoryDocument::new();
+    let a = document.create_element("a");
+    let b = document.create_element("b");
+    let nodes_ids = NodesIds::<MemoryDocument>::new();
+
+    let a_handle = nodes_ids.insert("0.a", a.clone());
+    let b_handle = nodes_ids.insert("0.b", b.clone());
+    assert_ne!(a_handle, b_handle);
+    assert_eq!(nodes_ids.insert("0.a", a.clone()), a_handle);
+    assert_eq!(nodes_ids.handle(&b), Some(b_handle));
+    assert_eq!(nodes_ids.id_handle("0.b"), Some(b_handle));
+    assert_eq!(nodes_ids.handle_id(b_handle), Some("0.b".to_string()));
+    assert_eq!(nodes_ids.handle_node(b_handle), Some(b.clone()));
+
+    assert_eq!(nodes_ids.remove_id("0.a"), Some(a.clone()));
+    assert_eq!(nodes_ids.handle(&a), None);
+    assert_eq!(nodes_ids.handle_id(a_handle), None);
+    assert_eq!(nodes_ids.insert("0.c", a.clone()), a_handle);
+    assert_eq!(nodes_ids.id(&a), Some("0.c".to_string()));
+    assert_eq!(nodes_ids.node("0.a"), None);
+}
+
+#[test]
+fn test_nodes_ids_child_handles() {
+    use super::MemoryDocument;
+
+    let document = MemoryDocument::new();
+    let list = document.create_element("ul");
+    let item = document.create_element("li");
+    let keyed = document.create_element("li");
+    let nodes_ids = NodesIds::<MemoryDocument>::new();
+    let key = "a.b".to_string();
+
+    let list_handle = nodes_ids.insert("0", list.clone());
+    let item_handle = nodes_ids.insert_child(list_handle, None, 1, item.clone());
+    let keyed_handle = nodes_ids.insert_child(list_handle, Some(&key), 2, keyed.clone());
+    assert_eq!(nodes_ids.child_handle(list_handle, None, 1), Some(item_handle));
+    assert_eq!(nodes_ids.child_handle(list_handle, Some("1"), 0), Some(item_handle));
+    assert_eq!(nodes_ids.id_handle("0.1"), Some(item_handle));
+    assert_eq!(nodes_ids.id_handle("0.a.b"), Some(keyed_handle));
+    assert_eq!(nodes_ids.id(&keyed), Some("0.a.b".to_string()));
+    assert_eq!(nodes_ids.insert("0.1", item.clone()), item_handle);
+    assert_eq!(
+        nodes_ids.view_id_string(ViewId::Child(item_handle, None, 3)),
+        "0.1.3".to_string()
+    );
+
+    // a forgotten parent keeps its handle while child ids hang off it
+    assert_eq!(nodes_ids.remove_handle(list_handle), Some(list.clone()));
+    assert_eq!(nodes_ids.node("0"), None);
+    assert_eq!(nodes_ids.id(&item), Some("0.1".to_string()));
+    assert_eq!(nodes_ids.insert("0", list.clone()), list_handle);
+
+    nodes_ids.remove_handle(list_handle);
+    nodes_ids.remove_handle(item_handle);
+    nodes_ids.remove_handle(keyed_handle);
+    assert_eq!(nodes_ids.id_handle("0.1"), None);
+    assert_eq!(nodes_ids.insert("1", list.clone()), list_handle);
+}
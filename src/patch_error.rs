@@ -0,0 +1,73 @@
+use std::error::Error;
+use std::fmt;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PatchErrorPolicy {
+    /// stop at the first failed patch and return its error
+    Abort,
+    /// warn, skip the failed patch and apply the remaining ones
+    Continue,
+    /// revert the patches already applied from the transaction, or batch, so
+    /// the dom is left as it was, and return the error
+    Rollback,
+}
+
+impl Default for PatchErrorPolicy {
+    #[inline(always)]
+    fn default() -> Self {
+        PatchErrorPolicy::Abort
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum PatchError {
+    /// a patch targets an id that has no node
+    UnknownId(String),
+    /// the node for the id is not attached to a parent
+    MissingParent(String),
+    /// a `Patch::Order` index is past the end of the node's children
+    OrderIndexOutOfRange { id: String, index: usize, len: usize },
+    /// a patch targets the children of a node holding raw html
+    OpaqueNode(String),
+    /// the dom threw while patching the node for the id
+    JsException { id: String, message: String },
+}
+
+impl PatchError {
+    #[inline]
+    pub fn id(&self) -> &str {
+        match self {
+            &PatchError::UnknownId(ref id) => id,
+            &PatchError::MissingParent(ref id) => id,
+            &PatchError::OrderIndexOutOfRange { ref id, .. } => id,
+            &PatchError::OpaqueNode(ref id) => id,
+            &PatchError::JsException { ref id, .. } => id,
+        }
+    }
+}
+
+impl fmt::Display for PatchError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            &PatchError::UnknownId(ref id) => write!(f, "no node for id {:?}", id),
+            &PatchError::MissingParent(ref id) => write!(f, "node {:?} has no parent", id),
+            &PatchError::OrderIndexOutOfRange {
+                ref id,
+                index,
+                len,
+            } => write!(
+                f,
+                "order index {} out of range for node {:?} with {} children",
+                index, id, len
+            ),
+            &PatchError::OpaqueNode(ref id) => {
+                write!(f, "node {:?} holds raw html, its children can not be patched", id)
+            }
+            &PatchError::JsException { ref id, ref message } => {
+                write!(f, "exception while patching node {:?}: {}", id, message)
+            }
+        }
+    }
+}
+
+impl Error for PatchError {}
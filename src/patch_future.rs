@@ -0,0 +1,41 @@
+use futures::{Async, Future, Poll};
+use futures::task;
+use stdweb::web::Document;
+
+use super::{Dom, PatchError, Scheduler};
+
+/// resolves once a `Scheduler` has patched every transaction scheduled
+/// before it was created, see `Scheduler::when_applied`, or fails with the
+/// error of the batch that held them
+pub struct PatchFuture<D: Dom = Document> {
+    scheduler: Scheduler<D>,
+    count: usize,
+}
+
+impl<D: Dom> PatchFuture<D> {
+    #[inline(always)]
+    pub fn new(scheduler: Scheduler<D>, count: usize) -> Self {
+        PatchFuture {
+            scheduler: scheduler,
+            count: count,
+        }
+    }
+}
+
+impl<D: Dom> Future for PatchFuture<D> {
+    type Item = ();
+    type Error = PatchError;
+
+    #[inline]
+    fn poll(&mut self) -> Poll<(), PatchError> {
+        if self.scheduler.is_applied(self.count) {
+            match self.scheduler.error(self.count) {
+                Some(error) => Err(error),
+                None => Ok(Async::Ready(())),
+            }
+        } else {
+            self.scheduler.wait(task::current());
+            Ok(Async::NotReady)
+        }
+    }
+}
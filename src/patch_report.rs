@@ -0,0 +1,49 @@
+use super::PatchError;
+
+/// what a patch did, counts of dom operations and the milliseconds spent in
+/// each phase, errors are only collected when the patcher's
+/// `PatchErrorPolicy` is `Continue`
+#[derive(Debug, Clone, Default)]
+pub struct PatchReport {
+    /// nodes built for mounted, inserted and replacing views
+    pub created: usize,
+    pub replaced: usize,
+    /// children moved by `Patch::Order`
+    pub moved: usize,
+    /// nodes of removed views
+    pub removed: usize,
+    pub attributes_set: usize,
+    pub attributes_removed: usize,
+    pub properties_set: usize,
+    pub properties_removed: usize,
+    pub styles_set: usize,
+    pub styles_removed: usize,
+    /// class names added to or removed from a `classList`
+    pub classes_added: usize,
+    pub classes_removed: usize,
+    /// delegated listeners added to or removed from the root and portal
+    /// containers
+    pub listeners_added: usize,
+    pub listeners_removed: usize,
+    pub patches_time: f64,
+    pub removes_time: f64,
+    pub events_time: f64,
+    pub errors: Vec<PatchError>,
+}
+
+impl PatchReport {
+    #[inline(always)]
+    pub fn new() -> Self {
+        PatchReport::default()
+    }
+
+    #[inline]
+    pub fn is_ok(&self) -> bool {
+        self.errors.is_empty()
+    }
+
+    #[inline]
+    pub fn total_time(&self) -> f64 {
+        self.patches_time + self.removes_time + self.events_time
+    }
+}
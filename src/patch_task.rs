@@ -0,0 +1,344 @@
+use stdweb::web::Document;
+use virtual_view::{Patch, RawView, Transaction};
+
+use super::{Batch, BatchPatch, Dom, Namespace, NodeBuilder, NodeId, PatchError, PatchReport,
+            Patcher, ViewId};
+use super::patcher::known_node;
+use super::raw_html::view_children;
+use super::rollback::Rollback;
+
+struct BuildFrame<N> {
+    node: N,
+    handle: NodeId,
+    next_child: usize,
+    namespace: Namespace,
+}
+
+struct DetachedBuild<N> {
+    target: Option<N>,
+    node: N,
+    frames: Vec<BuildFrame<N>>,
+}
+
+/// a transaction, or a `Batch` of them, patched a slice at a time, the
+/// subtree a patch creates is built detached and only put in the tree once
+/// complete, so the dom stays consistent between slices
+pub struct PatchTask<D: Dom = Document> {
+    batch: Batch,
+    index: usize,
+    build: Option<DetachedBuild<D::Node>>,
+    report: PatchReport,
+    rollback: Rollback<D>,
+}
+
+impl<D: Dom> PatchTask<D> {
+    #[inline(always)]
+    pub fn new(batch: Batch) -> Self {
+        PatchTask {
+            batch: batch,
+            index: 0,
+            build: None,
+            report: PatchReport::new(),
+            rollback: Rollback::new(),
+        }
+    }
+
+    #[inline]
+    pub fn from_transaction(transaction: &Transaction) -> Self {
+        let mut batch = Batch::new();
+        batch.push(transaction);
+        Self::new(batch)
+    }
+
+    /// what has been patched so far, the patcher's `last_report` once done
+    #[inline]
+    pub fn report(&self) -> &PatchReport {
+        &self.report
+    }
+
+    #[inline]
+    pub fn is_done(&self) -> bool {
+        self.index > self.batch.patches().len() + self.batch.removes().len()
+    }
+
+    /// patches until `budget` milliseconds have passed, at least one step is
+    /// always taken so every call makes progress, returns true once done
+    ///
+    /// a failed patch is handled by the patcher's `PatchErrorPolicy`, like in
+    /// `Patcher::try_patch`, a rollback reverts the slices already patched
+    #[inline]
+    pub fn run(&mut self, patcher: &mut Patcher<D>, budget: f64) -> Result<bool, PatchError> {
+        let start = patcher.document().now();
+
+        while !self.is_done() {
+            self.step(patcher)?;
+
+            if patcher.document().now() - start >= budget {
+                break;
+            }
+        }
+
+        if self.is_done() {
+            patcher.finish_report(self.report.clone());
+        }
+        Ok(self.is_done())
+    }
+
+    #[inline]
+    fn step(&mut self, patcher: &mut Patcher<D>) -> Result<(), PatchError> {
+        let start = patcher.document().now();
+        let patches_len = self.batch.patches().len();
+        let removes_len = self.batch.removes().len();
+
+        let is_remove = self.index < patches_len && self.batch.patches()[self.index].1.is_remove();
+
+        if self.index < patches_len && !is_remove {
+            if self.build.is_none() {
+                let (id, patch) = batch_patch(&self.batch, self.index);
+                self.rollback.record_patch(patcher, id, patch);
+            }
+            let (done, result) = match self.patch_step(patcher) {
+                Ok(done) => (done, Ok(())),
+                Err(error) => {
+                    self.discard_build(patcher);
+                    (true, Err(error))
+                }
+            };
+            let id = &self.batch.patches()[self.index].0;
+
+            if done {
+                self.index += 1;
+            }
+            let result = patcher.patch_result(&mut self.report, id, result);
+            let result = patcher.rollback_result(&mut self.rollback, result);
+            self.report.patches_time += patcher.document().now() - start;
+            result
+        } else if self.index < patches_len + removes_len {
+            let (id, view) = match self.batch.patches().get(self.index) {
+                Some(&(ref id, BatchPatch::Remove(ref view))) => (id, view),
+                _ => {
+                    let &(ref id, ref view) = &self.batch.removes()[self.index - patches_len];
+                    (id, view)
+                }
+            };
+            self.rollback.record_remove(patcher, id, view);
+            let result = patcher.apply_remove(id, &mut self.report);
+
+            self.index += 1;
+            let result = patcher.patch_result(&mut self.report, id, result);
+            let result = patcher.rollback_result(&mut self.rollback, result);
+            self.report.removes_time += patcher.document().now() - start;
+            result
+        } else {
+            for &(ref id, ref name, value) in self.batch.events() {
+                patcher.apply_event(id, name, value, &mut self.report);
+            }
+            self.index += 1;
+            self.report.events_time += patcher.document().now() - start;
+            Ok(())
+        }
+    }
+
+    /// creates one node of the current patch, or applies it whole when it
+    /// creates nothing, returns true once the patch is applied
+    #[inline]
+    fn patch_step(&mut self, patcher: &mut Patcher<D>) -> Result<bool, PatchError> {
+        let (id, patch) = batch_patch(&self.batch, self.index);
+
+        let (child_id, view) = match patch {
+            &Patch::Mount(ref view) => (id, view),
+            &Patch::Insert(ref child_id, _, ref view) => (child_id, view),
+            &Patch::Replace(_, ref view) => (id, view),
+            patch => {
+                return patcher
+                    .apply_patch(id, patch, &mut self.report)
+                    .map(|_| true)
+            }
+        };
+
+        if self.build.is_some() {
+            let build = self.build.as_mut().unwrap();
+            let builder = patcher.node_builder();
+            let report = &mut self.report;
+            build_child(patcher.document(), &builder, view, &mut build.frames, report);
+        } else {
+            let target = patcher.node(id);
+            let parent = match patch {
+                &Patch::Mount(_) => patcher.root().clone(),
+                &Patch::Insert(..) => patcher.children_node(id, target.as_ref())?.clone(),
+                _ => patcher.node_parent(id, known_node(id, target.as_ref())?)?,
+            };
+            let builder = patcher.node_builder();
+            let namespace = builder.children_namespace(&parent);
+            let report = &mut self.report;
+            let (handle, node) = builder.create(ViewId::Id(child_id), view, namespace, report);
+
+            self.build = Some(DetachedBuild {
+                target: target,
+                node: node.clone(),
+                frames: vec![
+                    BuildFrame {
+                        node: node,
+                        handle: handle,
+                        next_child: 0,
+                        namespace: children_namespace(view, namespace),
+                    },
+                ],
+            });
+        }
+
+        let done = {
+            let build = self.build.as_mut().unwrap();
+            pop_built_frames(view, &mut build.frames);
+            build.frames.is_empty()
+        };
+
+        if done {
+            {
+                let build = self.build.as_ref().unwrap();
+                let report = &mut self.report;
+                patcher.attach_node(id, build.target.as_ref(), patch, &build.node, report)?;
+            }
+            self.build = None;
+        }
+
+        Ok(done)
+    }
+
+    /// drops the detached subtree of a failed patch, forgetting the ids and
+    /// portals its nodes were given so none of them points at an orphan, the
+    /// node it was to replace gets its id back
+    #[inline]
+    fn discard_build(&mut self, patcher: &mut Patcher<D>) {
+        let build = match self.build.take() {
+            Some(build) => build,
+            None => return,
+        };
+        let (id, patch) = batch_patch(&self.batch, self.index);
+        let child_id = match patch {
+            &Patch::Insert(ref child_id, ..) => child_id,
+            _ => id,
+        };
+
+        patcher.remove_child_nodes_id(child_id);
+        patcher.detach_portals(&build.node);
+
+        if let (&Patch::Replace(..), Some(target)) = (patch, build.target) {
+            patcher.nodes_ids().insert(id, target);
+        }
+    }
+}
+
+/// the patch at `index` of the batch's patches, never a remove
+#[inline]
+fn batch_patch(batch: &Batch, index: usize) -> (&String, &Patch) {
+    match &batch.patches()[index] {
+        &(ref id, BatchPatch::Patch(ref patch)) => (id, patch),
+        &(_, BatchPatch::Remove(_)) => unreachable!("remove stepped as a patch"),
+    }
+}
+
+/// creates the next child of the innermost unfinished node
+#[inline]
+fn build_child<D: Dom>(
+    document: &D,
+    builder: &NodeBuilder<D>,
+    view: &RawView,
+    frames: &mut Vec<BuildFrame<D::Node>>,
+    report: &mut PatchReport,
+) {
+    let view = frame_view(view, frames);
+
+    let frame = {
+        let frame = frames.last_mut().unwrap();
+        let index = frame.next_child;
+        let child = &view_children(view)[index];
+        let child_id = ViewId::Child(frame.handle, child.key(), index);
+        let (child_handle, child_node) = builder.create(child_id, child, frame.namespace, report);
+
+        document.append_child(&frame.node, &builder.anchor(child, &child_node));
+        builder.restore_controlled(&frame.node);
+        frame.next_child += 1;
+
+        if view_children(child).is_empty() {
+            None
+        } else {
+            Some(BuildFrame {
+                node: child_node,
+                handle: child_handle,
+                next_child: 0,
+                namespace: children_namespace(child, frame.namespace),
+            })
+        }
+    };
+
+    if let Some(frame) = frame {
+        frames.push(frame);
+    }
+}
+
+#[inline]
+fn pop_built_frames<N>(view: &RawView, frames: &mut Vec<BuildFrame<N>>) {
+    while !frames.is_empty() {
+        let built = {
+            let frame = frames.last().unwrap();
+            frame.next_child >= view_children(frame_view(view, frames)).len()
+        };
+
+        if built {
+            frames.pop();
+        } else {
+            break;
+        }
+    }
+}
+
+/// the view of the innermost frame, every outer frame's last created child
+/// is the next frame's node
+#[inline]
+fn frame_view<'a, N>(view: &'a RawView, frames: &[BuildFrame<N>]) -> &'a RawView {
+    let mut view = view;
+
+    for frame in &frames[..frames.len() - 1] {
+        view = &view_children(view)[frame.next_child - 1];
+    }
+
+    view
+}
+
+#[inline]
+fn children_namespace(view: &RawView, namespace: Namespace) -> Namespace {
+    match view {
+        &RawView::Data { ref kind, .. } => namespace.element(kind).children(kind),
+        &RawView::Text(_) => namespace,
+    }
+}
+
+#[test]
+fn test_patch_task() {
+    use super::tests::memory_patcher;
+
+    let (_, root, mut patcher) = memory_patcher();
+    let view = view! {
+        <ul>
+            <li>{"a"}</li>
+            <li>{"b"}</li>
+        </ul>
+    };
+    let html = "<ul><li>a</li><li>b</li></ul>";
+
+    let mut batch = Batch::new();
+    batch.push_patch(&"0".to_string(), &Patch::Mount((&view).into()));
+    let mut task = PatchTask::new(batch);
+    let mut slices = 1;
+
+    while !task.run(&mut patcher, 0.0).unwrap() {
+        let inner_html = root.inner_html();
+        assert!(inner_html == "" || inner_html == html);
+        slices += 1;
+    }
+
+    assert_eq!(slices, 6);
+    assert_eq!(root.inner_html(), html);
+    assert_eq!(patcher.id(&root.child(0).unwrap()), Some("0".to_string()));
+}
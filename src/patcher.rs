@@ -1,307 +1,1794 @@
-use std::collections::HashMap;
+use std::vec::IntoIter;
 
-use fnv::FnvHashMap;
 use serde_json::{Map, Value};
-use stdweb;
-use stdweb::unstable::TryInto;
-use stdweb::web::{Document, INode, Node};
-use virtual_view::{view_id, EventManager, Patch, RawView, Transaction};
-
-use super::{Events, NodesIds, ToHtmlString};
-
-pub struct Patcher {
-    root: Node,
-    document: Document,
-    events: Events,
-    nodes_ids: NodesIds,
+use stdweb::web::Document;
+use virtual_view::{EventManager, Patch, Prop, RawView, Transaction};
+
+use super::{Batch, BatchPatch, Dom, Events, HydrationError, HydrationMismatch,
+            HydrationMismatchKind, HydrationPolicy, NodeBuilder, NodeId, NodesIds, PatchError,
+            PatchErrorPolicy, PatchReport, Recorder, RefChange, TextMode, ViewId};
+use super::controlled::{controls, set_controlled};
+use super::portal::portal_target;
+use super::reorder::{order_nodes, reorder_children};
+use super::style::style_declarations;
+use super::rollback::Rollback;
+use super::prop_kind::{is_property, props_attributes};
+use super::props::{remove_prop, set_attribute, update_prop};
+use super::raw_html::view_raw_html;
+use super::refs::{ref_name, REF_PROP};
+
+pub struct Patcher<D: Dom = Document> {
+    root: D::Node,
+    document: D,
+    events: Events<D>,
+    nodes_ids: NodesIds<D>,
+    hydration_policy: HydrationPolicy,
+    error_policy: PatchErrorPolicy,
+    text_mode: TextMode,
+    last_report: PatchReport,
+    ref_listener: Option<Box<Fn(&RefChange)>>,
 }
 
-impl Patcher {
+impl<D: Dom> Patcher<D> {
     #[inline(always)]
-    pub fn new(root: Node, document: Document, event_manager: EventManager) -> Self {
+    pub fn new(root: D::Node, document: D, event_manager: EventManager) -> Self {
+        let events = Events::new(root.clone(), event_manager);
+
         Patcher {
             root: root,
             document: document,
-            events: Events::new(event_manager),
+            events: events,
             nodes_ids: NodesIds::new(),
+            hydration_policy: HydrationPolicy::default(),
+            error_policy: PatchErrorPolicy::default(),
+            text_mode: TextMode::default(),
+            last_report: PatchReport::new(),
+            ref_listener: None,
         }
     }
 
+    /// a patcher mounting into the open shadow root of `host`, attaching one
+    /// if `host` has none, so the page's styles do not reach its nodes,
+    /// `document` should be the one `host` belongs to, errors with what the
+    /// dom threw when `host` can not have a shadow root
     #[inline]
-    pub fn node(&self, id: &str) -> Option<Node> {
+    pub fn with_shadow_root(
+        host: &D::Node,
+        document: D,
+        event_manager: EventManager,
+    ) -> Result<Self, String> {
+        let root = document.attach_shadow(host)?;
+        Ok(Self::new(root, document, event_manager))
+    }
+
+    #[inline]
+    pub fn root(&self) -> &D::Node {
+        &self.root
+    }
+    #[inline]
+    pub fn document(&self) -> &D {
+        &self.document
+    }
+
+    #[inline]
+    pub fn node(&self, id: &str) -> Option<D::Node> {
         self.nodes_ids.node(id)
     }
     #[inline]
-    pub fn id(&self, node: &Node) -> Option<String> {
+    pub fn id(&self, node: &D::Node) -> Option<String> {
         self.nodes_ids.id(node)
     }
 
+    /// the element given the ref prop `name`, if it is mounted
+    #[inline]
+    pub fn ref_node(&self, name: &str) -> Option<D::Node> {
+        self.nodes_ids.ref_node(name)
+    }
+    /// the view id of the element given the ref prop `name`
+    #[inline]
+    pub fn ref_id(&self, name: &str) -> Option<String> {
+        self.ref_node(name).and_then(|node| self.nodes_ids.id(&node))
+    }
+    /// calls `ref_listener` with every ref mounted, replaced or removed, after
+    /// the patches causing it are applied
+    #[inline]
+    pub fn set_ref_listener(&mut self, ref_listener: Option<Box<Fn(&RefChange)>>) {
+        self.ref_listener = ref_listener;
+    }
+
+    /// applies `transaction`, panicking if a patch can not be applied, see
+    /// `try_patch`
     #[inline]
     pub fn patch(&mut self, transaction: &Transaction) {
-        for (id, patches) in transaction.patches() {
-            let node = self.nodes_ids.node(id);
+        expect_patched(self.try_patch(transaction));
+    }
 
+    /// applies `transaction`, returning the first patch that fails instead of
+    /// panicking, or with `PatchErrorPolicy::Continue` skipping failed patches
+    /// and collecting their errors in the report, with
+    /// `PatchErrorPolicy::Rollback` nothing of a failed transaction is applied
+    #[inline]
+    pub fn try_patch(&mut self, transaction: &Transaction) -> Result<PatchReport, PatchError> {
+        let mut report = PatchReport::new();
+        let mut rollback = Rollback::new();
+        self.document.take_exception();
+        self.record_transaction(transaction);
+
+        let start = self.document.now();
+        for (id, patches) in transaction.patches() {
             for patch in patches {
-                self.apply_patch(id, node.as_ref(), patch);
+                rollback.record_patch(self, id, patch);
+                let result = self.apply_patch(id, patch, &mut report);
+                let result = self.patch_result(&mut report, id, result);
+                self.rollback_result(&mut rollback, result)?;
             }
         }
+        let patched = self.document.now();
         for (id, view) in transaction.removes() {
-            if let Some(node) = self.nodes_ids.node(id) {
-                let parent = node.parent_node().expect("node has no parent");
-                let _ = parent.remove_child(&node);
+            rollback.record_remove(self, id, view);
+            let result = self.apply_remove(id, &mut report);
+            let result = self.patch_result(&mut report, id, result);
+            self.rollback_result(&mut rollback, result)?;
+        }
+        let removed = self.document.now();
+        self.apply_events(transaction, &mut report);
+
+        report.patches_time = patched - start;
+        report.removes_time = removed - patched;
+        report.events_time = self.document.now() - removed;
+
+        Ok(self.finish_report(report))
+    }
+
+    /// applies every transaction merged into `batch` as if they were patched
+    /// one after another, see `Scheduler`
+    #[inline]
+    pub fn patch_batch(&mut self, batch: &Batch) {
+        expect_patched(self.try_patch_batch(batch));
+    }
+
+    #[inline]
+    pub fn try_patch_batch(&mut self, batch: &Batch) -> Result<PatchReport, PatchError> {
+        let mut report = PatchReport::new();
+        let mut rollback = Rollback::new();
+        self.document.take_exception();
+
+        let start = self.document.now();
+        for &(ref id, ref patch) in batch.patches() {
+            let result = match patch {
+                &BatchPatch::Patch(ref patch) => {
+                    rollback.record_patch(self, id, patch);
+                    self.apply_patch(id, patch, &mut report)
+                }
+                &BatchPatch::Remove(ref view) => {
+                    rollback.record_remove(self, id, view);
+                    self.apply_remove(id, &mut report)
+                }
+            };
+            let result = self.patch_result(&mut report, id, result);
+            self.rollback_result(&mut rollback, result)?;
+        }
+        let patched = self.document.now();
+        for &(ref id, ref view) in batch.removes() {
+            rollback.record_remove(self, id, view);
+            let result = self.apply_remove(id, &mut report);
+            let result = self.patch_result(&mut report, id, result);
+            self.rollback_result(&mut rollback, result)?;
+        }
+        let removed = self.document.now();
+        for &(ref id, ref name, value) in batch.events() {
+            self.apply_event(id, name, value, &mut report);
+        }
+
+        report.patches_time = patched - start;
+        report.removes_time = removed - patched;
+        report.events_time = self.document.now() - removed;
+
+        Ok(self.finish_report(report))
+    }
+
+    /// the report of the last transaction, batch or task patched, or hydrated
+    #[inline]
+    pub fn last_report(&self) -> &PatchReport {
+        &self.last_report
+    }
+
+    /// applies the first transaction over markup already rendered into `root`,
+    /// usually by `ToHtmlString` on the server, reusing the existing nodes and
+    /// only touching the dom where it differs from the mounted views
+    ///
+    /// returns every mismatch found, or the first one as an error when the
+    /// hydration policy is `HydrationPolicy::Fail`, the other patches and the
+    /// removes fail as the `PatchErrorPolicy` says, though nothing is rolled
+    /// back
+    #[inline]
+    pub fn hydrate(
+        &mut self,
+        transaction: &Transaction,
+    ) -> Result<Vec<HydrationMismatch>, HydrationError> {
+        let mut mismatches = Vec::new();
+        let mut report = PatchReport::new();
+        self.record_transaction(transaction);
+        let mut root_nodes = self.hydration_child_nodes(&self.root).into_iter();
+
+        for (id, patches) in transaction.patches() {
+            for patch in patches {
+                if let &Patch::Mount(ref view) = patch {
+                    let root = self.root.clone();
+                    let root_node = self.next_hydration_node(&root, &mut root_nodes, view);
+                    let root_node = root_node.as_ref();
+                    let root_id = ViewId::Id(id);
+                    let report = &mut report;
+                    self.hydrate_node(&root, root_node, root_id, view, &mut mismatches, report)?;
+                } else {
+                    let result = self.apply_patch(id, patch, &mut report);
+                    self.patch_result(&mut report, id, result)?;
+                }
             }
-            self.remove_child_nodes_id(id, &view);
         }
+        self.hydrate_extra_nodes(&self.root, ViewId::Id(""), root_nodes, &mut mismatches)?;
+
+        for (id, _) in transaction.removes() {
+            let result = self.apply_remove(id, &mut report);
+            self.patch_result(&mut report, id, result)?;
+        }
+        self.apply_events(transaction, &mut report);
+        self.finish_report(report);
+
+        Ok(mismatches)
+    }
+
+    /// dispatches `event` for the view `id` as if the dom had, `name` is the
+    /// dom event name like `click`
+    #[inline]
+    pub fn dispatch_event(&self, id: &str, name: &str, event: Prop) {
+        self.events.dispatch(&self.document, id, name, event);
+    }
+
+    #[inline]
+    pub fn recorder(&self) -> Option<Recorder> {
+        self.events.recorder()
+    }
+    /// records every transaction patched and event dispatched from now on
+    /// into `recorder`, see `Recording::replay`
+    #[inline]
+    pub fn set_recorder(&mut self, recorder: Option<Recorder>) {
+        self.events.set_recorder(recorder);
+    }
+
+    #[inline]
+    pub(crate) fn record_transaction(&self, transaction: &Transaction) {
+        if let Some(recorder) = self.events.recorder() {
+            if let Err(error) = recorder.record_transaction(transaction) {
+                self.document.warn(&error.to_string());
+            }
+        }
+    }
+
+    /// removes the listeners this patcher added to its root and portal
+    /// containers, leaving those of anything else on the page, returns how
+    /// many were removed, dropping the patcher does the same
+    #[inline]
+    pub fn teardown(&mut self) -> usize {
+        self.events.clear(&self.document)
+    }
+
+    #[inline]
+    pub fn hydration_policy(&self) -> HydrationPolicy {
+        self.hydration_policy
+    }
+    #[inline]
+    pub fn set_hydration_policy(&mut self, hydration_policy: HydrationPolicy) {
+        self.hydration_policy = hydration_policy;
+    }
+
+    #[inline]
+    pub fn error_policy(&self) -> PatchErrorPolicy {
+        self.error_policy
+    }
+    #[inline]
+    pub fn set_error_policy(&mut self, error_policy: PatchErrorPolicy) {
+        self.error_policy = error_policy;
+    }
+
+    #[inline]
+    pub fn text_mode(&self) -> TextMode {
+        self.text_mode
+    }
+    /// how text views are created, should match the `TextMode` used by
+    /// `ToHtmlString` for markup that is hydrated
+    #[inline]
+    pub fn set_text_mode(&mut self, text_mode: TextMode) {
+        self.text_mode = text_mode;
+    }
+
+    #[inline]
+    fn apply_events(&mut self, transaction: &Transaction, report: &mut PatchReport) {
         for (id, events) in transaction.events() {
             for (name, value) in events {
-                let node_option = self.nodes_ids.node(id);
+                self.apply_event(id, name, *value, report);
+            }
+        }
+    }
 
-                if let Some(node) = node_option {
-                    let name = &name[2..];
+    /// also delegates events from the containers portals are mounted into,
+    /// and stops for those the last portal left
+    #[inline]
+    pub(crate) fn finish_report(&mut self, mut report: PatchReport) -> PatchReport {
+        let containers = self.nodes_ids.portal_containers();
+        let root_key = D::node_key(&self.root);
 
-                    if *value {
-                        self.events
-                            .listen(name, id, &node, &self.nodes_ids, &self.document);
-                    } else {
-                        self.events
-                            .unlisten(name, id, &node, &self.nodes_ids, &self.document);
-                    }
+        for root in self.events.roots() {
+            let key = D::node_key(&root);
+
+            if key != root_key && !containers.iter().any(|c| D::node_key(c) == key) {
+                report.listeners_removed += self.events.remove_root(&root, &self.document);
+            }
+        }
+        for container in containers {
+            let nodes_ids = &self.nodes_ids;
+            report.listeners_added += self.events.add_root(container, nodes_ids, &self.document);
+        }
+        if self.nodes_ids.has_controlled() {
+            let nodes_ids = &self.nodes_ids;
+            report.listeners_added += self.events.listen_controlled(nodes_ids, &self.document);
+        }
+        for ref_change in self.nodes_ids.take_ref_changes() {
+            if let Some(ref ref_listener) = self.ref_listener {
+                ref_listener(&ref_change);
+            }
+        }
+        self.last_report = report.clone();
+        report
+    }
+
+    /// turns the result of a patch, and any exception the dom swallowed
+    /// while applying it, into an error or a report entry depending on the
+    /// error policy
+    #[inline]
+    pub(crate) fn patch_result(
+        &self,
+        report: &mut PatchReport,
+        id: &str,
+        result: Result<(), PatchError>,
+    ) -> Result<(), PatchError> {
+        let exception = self.document.take_exception();
+        let result = result.and_then(|_| match exception {
+            Some(message) => Err(PatchError::JsException {
+                id: id.into(),
+                message: message,
+            }),
+            None => Ok(()),
+        });
+
+        match result {
+            Ok(()) => Ok(()),
+            Err(error) => match self.error_policy {
+                PatchErrorPolicy::Abort | PatchErrorPolicy::Rollback => Err(error),
+                PatchErrorPolicy::Continue => {
+                    self.document.warn(&error.to_string());
+                    report.errors.push(error);
+                    Ok(())
                 }
+            },
+        }
+    }
+
+    /// reverts everything `rollback` recorded when `result` failed
+    #[inline]
+    pub(crate) fn rollback_result(
+        &mut self,
+        rollback: &mut Rollback<D>,
+        result: Result<(), PatchError>,
+    ) -> Result<(), PatchError> {
+        if result.is_err() {
+            rollback.undo(self);
+        }
+        result
+    }
+
+    #[inline]
+    pub(crate) fn apply_remove(
+        &mut self,
+        id: &String,
+        report: &mut PatchReport,
+    ) -> Result<(), PatchError> {
+        if let Some(node) = self.nodes_ids.node(id) {
+            let anchor = self.nodes_ids.anchor(&node);
+            let parent = self.node_parent(id, &anchor)?;
+            self.document.remove_child(&parent, &anchor);
+            report.removed += self.remove_child_nodes_id(id);
+            self.detach_portals(&anchor);
+        }
+        Ok(())
+    }
+    #[inline]
+    pub(crate) fn apply_event(
+        &mut self,
+        id: &String,
+        name: &str,
+        value: bool,
+        report: &mut PatchReport,
+    ) {
+        if self.nodes_ids.node(id).is_some() {
+            let name = &name[2..];
+
+            if value {
+                report.listeners_added += self.events.listen(name, &self.nodes_ids, &self.document);
+            } else {
+                report.listeners_removed += self.events.unlisten(name, &self.document);
             }
         }
     }
 
     #[inline]
-    fn apply_patch(&mut self, id: &String, node: Option<&Node>, patch: &Patch) {
+    pub(crate) fn apply_patch(
+        &mut self,
+        id: &String,
+        patch: &Patch,
+        report: &mut PatchReport,
+    ) -> Result<(), PatchError> {
+        let node = self.nodes_ids.node(id);
+
         match patch {
             &Patch::Mount(ref view) => {
-                let new_node = self.create_node(id, view);
-                self.root.append_child(&new_node);
+                let root = self.root.clone();
+                let new_node = self.create_node(&root, ViewId::Id(id), view, report);
+                self.attach_node(id, node.as_ref(), patch, &new_node, report)
             }
-            &Patch::Insert(ref child_id, index, ref view) => {
-                let new_node = self.create_node(child_id, view);
-                let node = node.unwrap();
-
-                if let Some(next_node) = node.child_nodes().iter().nth(index + 1) {
-                    let _ = node.insert_before(&new_node, &next_node);
-                } else {
-                    node.append_child(&new_node);
-                }
+            &Patch::Insert(ref child_id, _, ref view) => {
+                let parent = self.children_node(id, node.as_ref())?;
+                let new_node = self.create_node(parent, ViewId::Id(child_id), view, report);
+                self.attach_node(id, Some(parent), patch, &new_node, report)
             }
             &Patch::Replace(ref _prev_view, ref next_view) => {
-                let new_node = self.create_node(id, next_view);
-                let old_node = node.expect("node is not in tree");
-                let parent = old_node.parent_node().expect("node has no parent");
-                let _ = parent.replace_child(&new_node, old_node);
+                let old_node = known_node(id, node.as_ref())?;
+                let parent = self.node_parent(id, old_node)?;
+                let new_node = self.create_node(&parent, ViewId::Id(id), next_view, report);
+                self.attach_node(id, Some(old_node), patch, &new_node, report)
             }
             &Patch::Order(ref order) => {
-                let parent_node = node.unwrap();
-                let child_nodes: Vec<_> = parent_node.child_nodes().iter().collect();
-                let mut key_map = FnvHashMap::default();
-
-                for &(index, ref key) in order.removes() {
-                    let child_node = &child_nodes[index];
+                let parent_node = self.children_node(id, node.as_ref())?;
+                let child_nodes = self.document.child_nodes(parent_node);
+                let nodes = order_nodes(id, child_nodes, order.removes(), order.inserts())?;
 
-                    if let &Some(ref key) = key {
-                        key_map.insert(key.clone(), child_node);
-                    }
-                    let _ = parent_node.remove_child(child_node);
-                }
-
-                let mut len = child_nodes.len();
-                for &(ref key, index) in order.inserts() {
-                    if let &Some(ref key) = key {
-                        let node = key_map[key];
-
-                        if index >= len {
-                            parent_node.append_child(node);
-                        } else {
-                            let _ = parent_node.insert_before(node, &child_nodes[index]);
-                        }
-                        len += 1;
-                    }
-                }
+                report.moved += reorder_children(&self.document, parent_node, &nodes);
+                Ok(())
             }
             &Patch::Props(ref prev_props, ref diff_props) => {
-                let node = node.unwrap();
+                let node = known_node(id, node.as_ref())?;
 
                 for (key, value) in diff_props {
+                    let prev_prop = prev_props.get(key).unwrap_or(&Value::Null);
+
                     if value.is_null() {
-                        Self::remove_prop(node, key, prev_props);
-                    } else if value.is_object() {
-                        Self::update_props(node, key, value);
+                        remove_prop(&self.document, &self.nodes_ids, node, key, prev_prop, report);
                     } else {
-                        let value = value.to_string();
-                        js! {
-                            var node = @{node};
-                            node.setAttribute(@{key}, @{value});
-                        }
+                        let nodes_ids = &self.nodes_ids;
+                        update_prop(&self.document, nodes_ids, node, key, prev_prop, value, report);
                     }
                 }
+                Ok(())
             }
         }
     }
 
+    /// puts `new_node`, created for a mount, insert or replace patch, in the tree
     #[inline]
-    fn remove_prop(node: &Node, key: &String, prev_props: &Map<String, Value>) {
-        let prev_prop = &prev_props[key];
+    pub(crate) fn attach_node(
+        &self,
+        id: &str,
+        node: Option<&D::Node>,
+        patch: &Patch,
+        new_node: &D::Node,
+        report: &mut PatchReport,
+    ) -> Result<(), PatchError> {
+        let builder = self.node_builder();
 
-        if key == "attributes" {
-            if let &Value::Object(ref map) = prev_prop {
-                for (attr_key, _) in map {
-                    js! {
-                        var node = @{node}, attr_key = @{attr_key};
-                        node.removeAttribute(attr_key);
-                    }
-                }
+        match patch {
+            &Patch::Mount(ref view) => {
+                let anchor = builder.anchor(view, new_node);
+                self.document.append_child(&self.root, &anchor);
             }
-        } else if key == "style" {
-            if let &Value::Object(ref map) = prev_prop {
-                for (attr_key, _) in map {
-                    js! {
-                        var node = @{node}, attr_key = @{attr_key};
-                        node.style[attr_key] = ""
-                    }
+            &Patch::Insert(_, index, ref view) => {
+                let node = known_node(id, node)?;
+                let anchor = builder.anchor(view, new_node);
+
+                if let Some(next_node) = self.document.child_nodes(node).get(index + 1) {
+                    self.document.insert_before(node, &anchor, next_node);
+                } else {
+                    self.document.append_child(node, &anchor);
                 }
+                builder.restore_controlled(node);
             }
-        } else if prev_prop.is_string() {
-            js! {
-                var node = @{node},
-                    key = @{key};
-
-                node[key] = "";
+            &Patch::Replace(_, ref view) => {
+                let old_node = known_node(id, node)?;
+                let old_anchor = self.nodes_ids.anchor(old_node);
+                let parent = self.node_parent(id, &old_anchor)?;
+                let anchor = builder.anchor(view, new_node);
+                self.document.replace_child(&parent, &anchor, &old_anchor);
+                self.detach_portals(&old_anchor);
+                report.replaced += 1;
             }
-        } else {
-            js! {
-                var node = @{node},
-                    key = @{key};
+            _ => (),
+        }
+        Ok(())
+    }
 
-                node[key] = null;
+    /// takes the portals of the subtree of `node`, which left the tree, out
+    /// of their containers
+    #[inline]
+    pub(crate) fn detach_portals(&self, node: &D::Node) {
+        detach_portals(&self.document, &self.nodes_ids, node);
+    }
+
+    /// the placeholder, portal and container of every portal attached in the
+    /// subtree of `node`, so they can be put back by `reattach_portals`
+    #[inline]
+    pub(crate) fn attached_portals(&self, node: &D::Node) -> Vec<(D::Node, D::Node, D::Node)> {
+        let mut portals = Vec::new();
+
+        if self.nodes_ids.has_portals() {
+            self.push_attached_portals(node, &mut portals);
+        }
+        portals
+    }
+    #[inline]
+    fn push_attached_portals(
+        &self,
+        node: &D::Node,
+        portals: &mut Vec<(D::Node, D::Node, D::Node)>,
+    ) {
+        if let Some(portal) = self.nodes_ids.portal(node) {
+            if let Some(container) = self.document.parent_node(&portal) {
+                self.push_attached_portals(&portal, portals);
+                portals.push((node.clone(), portal, container));
             }
         }
+        for child_node in self.document.child_nodes(node) {
+            self.push_attached_portals(&child_node, portals);
+        }
     }
     #[inline]
-    fn update_props(node: &Node, key: &String, value: &Value) {
-        if key == "attributes" {
-            if let &Value::Object(ref map) = value {
-                for (attr_key, attr_value) in map {
-                    let attr_value = json_to_js_value(attr_value);
-                    js! {
-                        var node = @{node},
-                            attr_key = @{attr_key},
-                            attr_value = @{attr_value};
+    pub(crate) fn reattach_portals(&self, portals: Vec<(D::Node, D::Node, D::Node)>) {
+        for (placeholder, portal, container) in portals {
+            self.document.append_child(&container, &portal);
+            self.nodes_ids.insert_portal(placeholder, portal, &container);
+        }
+    }
 
-                        node.setAttribute(attr_key, attr_value);
-                    }
+    /// `node` as the parent of patched children, which a node holding raw
+    /// html can not be
+    #[inline]
+    pub(crate) fn children_node<'a>(
+        &self,
+        id: &str,
+        node: Option<&'a D::Node>,
+    ) -> Result<&'a D::Node, PatchError> {
+        let node = known_node(id, node)?;
+
+        if self.nodes_ids.is_opaque(node) {
+            Err(PatchError::OpaqueNode(id.into()))
+        } else {
+            Ok(node)
+        }
+    }
+    #[inline]
+    pub(crate) fn node_parent(&self, id: &str, node: &D::Node) -> Result<D::Node, PatchError> {
+        self.document
+            .parent_node(node)
+            .ok_or_else(|| PatchError::MissingParent(id.into()))
+    }
+
+    #[inline]
+    fn create_node(
+        &mut self,
+        parent: &D::Node,
+        id: ViewId,
+        view: &RawView,
+        report: &mut PatchReport,
+    ) -> D::Node {
+        let builder = self.node_builder();
+        let namespace = builder.children_namespace(parent);
+        builder.build(id, view, namespace, report)
+    }
+    #[inline]
+    pub(crate) fn nodes_ids(&self) -> &NodesIds<D> {
+        &self.nodes_ids
+    }
+    #[inline]
+    pub(crate) fn node_builder(&self) -> NodeBuilder<D> {
+        NodeBuilder::new(&self.document, &self.nodes_ids, self.text_mode)
+    }
+
+    #[inline]
+    fn hydrate_node(
+        &mut self,
+        parent: &D::Node,
+        node: Option<&D::Node>,
+        id: ViewId,
+        view: &RawView,
+        mismatches: &mut Vec<HydrationMismatch>,
+        report: &mut PatchReport,
+    ) -> Result<(), HydrationMismatch> {
+        let node = match node {
+            Some(node) => node,
+            None => {
+                let mismatch = HydrationMismatch::new(
+                    &self.nodes_ids.view_id_string(id),
+                    HydrationMismatchKind::MissingChild,
+                    Some(view),
+                    None,
+                );
+                self.hydration_mismatch(mismatches, mismatch)?;
+                let new_node = self.create_node(parent, id, view, report);
+                let anchor = self.node_builder().anchor(view, &new_node);
+                self.document.append_child(parent, &anchor);
+                return Ok(());
+            }
+        };
+
+        let node_mismatches = self.hydration_node_mismatches(parent, node, id, view);
+
+        if !node_mismatches.is_empty() {
+            let remount = self.hydration_policy == HydrationPolicy::Remount
+                || node_mismatches[0].kind == HydrationMismatchKind::Kind;
+
+            for mismatch in node_mismatches {
+                self.hydration_mismatch(mismatches, mismatch)?;
+            }
+            if remount {
+                let new_node = self.create_node(parent, id, view, report);
+                let anchor = self.node_builder().anchor(view, &new_node);
+                self.document.replace_child(parent, &anchor, node);
+                return Ok(());
+            }
+        }
+
+        let handle = self.nodes_ids.insert_view_id(id, node.clone());
+
+        match view {
+            &RawView::Text(ref text) => {
+                if &self.document.text_content(node) != text {
+                    self.document.set_text_content(node, text);
                 }
             }
-        } else if key == "style" {
-            if let &Value::Object(ref map) = value {
-                for (attr_key, attr_value) in map {
-                    let attr_value = json_to_js_value(attr_value);
-                    js! {
-                        var node = @{node},
-                            attr_key = @{attr_key},
-                            attr_value = @{attr_value};
-
-                        node.style[attr_key] = attr_value;
+            &RawView::Data {
+                ref kind,
+                ref props,
+                ref children,
+                ..
+            } => {
+                self.hydrate_props(node, kind, props, report);
+
+                // the server rendered raw html as is, it is kept without comparing
+                if view_raw_html(view).is_some() {
+                    self.nodes_ids.set_opaque(node, true);
+                } else {
+                    let mut child_nodes = self.hydration_child_nodes(node).into_iter();
+
+                    for (index, child) in children.iter().enumerate() {
+                        let child_id = ViewId::Child(handle, child.key(), index);
+                        let child_node = self.next_hydration_node(node, &mut child_nodes, child);
+                        let child_node = child_node.as_ref();
+                        self.hydrate_node(node, child_node, child_id, child, mismatches, report)?;
                     }
+                    self.hydrate_extra_nodes(node, id, child_nodes, mismatches)?;
+                }
+
+                if portal_target(view).is_some() {
+                    self.hydrate_portal(parent, node, view);
                 }
             }
+        }
+
+        Ok(())
+    }
+
+    /// moves a portal the server rendered in place into its container,
+    /// leaving its placeholder where it was
+    #[inline]
+    fn hydrate_portal(&self, parent: &D::Node, node: &D::Node, view: &RawView) {
+        let key = D::node_key(node);
+        let next_node = self.document
+            .child_nodes(parent)
+            .into_iter()
+            .skip_while(|child_node| D::node_key(child_node) != key)
+            .nth(1);
+        let anchor = self.node_builder().anchor(view, node);
+
+        if D::node_key(&anchor) != key {
+            match next_node {
+                Some(ref next_node) => self.document.insert_before(parent, &anchor, next_node),
+                None => self.document.append_child(parent, &anchor),
+            }
+        }
+    }
+
+    #[inline]
+    fn hydration_node_mismatches(
+        &self,
+        parent: &D::Node,
+        node: &D::Node,
+        id: ViewId,
+        view: &RawView,
+    ) -> Vec<HydrationMismatch> {
+        let mut kinds = Vec::new();
+
+        if !self.hydration_kind_matches(parent, node, view) {
+            kinds.push(HydrationMismatchKind::Kind);
         } else {
-            let value = json_to_js_value(value);
-            js! {
-                var node = @{node},
-                    key = @{key},
-                    value = @{value};
+            match view {
+                &RawView::Text(ref text) => if &self.document.text_content(node) != text {
+                    kinds.push(HydrationMismatchKind::Text);
+                },
+                &RawView::Data {
+                    ref kind,
+                    ref props,
+                    ..
+                } => {
+                    let attributes = props_attributes(Some(kind), props);
+
+                    for name in self.document.attribute_names(node) {
+                        if !attributes.iter().any(|&(ref key, _)| key == &name) {
+                            kinds.push(HydrationMismatchKind::Attribute(name));
+                        }
+                    }
+                    for (key, value) in attributes {
+                        let actual = self.document.attribute(node, &key);
 
-                node[key] = value;
+                        let matches = match actual {
+                            // browsers reformat the style attribute, so compare declarations
+                            Some(ref actual) if key == "style" => {
+                                style_declarations(&Value::String(actual.clone()))
+                                    == style_declarations(&Value::String(value))
+                            }
+                            actual => actual == Some(value),
+                        };
+                        if !matches {
+                            kinds.push(HydrationMismatchKind::Attribute(key));
+                        }
+                    }
+                }
             }
         }
+
+        if kinds.is_empty() {
+            return Vec::new();
+        }
+        let id = self.nodes_ids.view_id_string(id);
+
+        kinds
+            .into_iter()
+            .map(|kind| {
+                let actual = self.document.outer_html(node);
+                HydrationMismatch::new(&id, kind, Some(view), Some(actual))
+            })
+            .collect()
     }
 
     #[inline]
-    fn create_node(&mut self, id: &String, view: &RawView) -> Node {
-        match view {
-            &RawView::Text(ref text) => {
-                let node: Node = self.document.create_element("span").unwrap().into();
-                node.set_text_content(text);
-                self.nodes_ids.insert(id.clone(), node.clone());
-                node
+    fn hydration_mismatch(
+        &self,
+        mismatches: &mut Vec<HydrationMismatch>,
+        mismatch: HydrationMismatch,
+    ) -> Result<(), HydrationMismatch> {
+        if self.hydration_policy == HydrationPolicy::Fail {
+            Err(mismatch)
+        } else {
+            self.document.warn(&mismatch.to_string());
+            mismatches.push(mismatch);
+            Ok(())
+        }
+    }
+
+    #[inline]
+    fn hydrate_props(
+        &self,
+        node: &D::Node,
+        kind: &str,
+        props: &Map<String, Value>,
+        report: &mut PatchReport,
+    ) {
+        let attributes = props_attributes(Some(kind), props);
+
+        for name in self.document.attribute_names(node) {
+            if !attributes.iter().any(|&(ref key, _)| key == &name) {
+                self.document.remove_attribute(node, &name);
+                report.attributes_removed += 1;
+            }
+        }
+        for (key, value) in attributes {
+            if self.document.attribute(node, &key).as_ref() != Some(&value) {
+                set_attribute(&self.document, node, &key, &value);
+                report.attributes_set += 1;
+            }
+        }
+        for (key, value) in props {
+            if key == REF_PROP {
+                if let Some(name) = ref_name(value) {
+                    self.nodes_ids.set_ref(name, node);
+                }
+            } else if controls(&self.document, node, key) {
+                set_controlled(&self.document, &self.nodes_ids, node, key, value);
+                report.properties_set += 1;
+            } else if is_property(Some(kind), key, value) {
+                self.document.set_property(node, key, value);
+                report.properties_set += 1;
             }
-            &RawView::Data { .. } => {
-                let tmp = self.document.create_element("div").unwrap();
-
-                let result = js!{
-                    var tmp = @{tmp};
-                    tmp.innerHTML = @{view.to_html_string()};
-                    return tmp.childNodes[0];
-                };
-
-                let node = result.into_reference().unwrap().downcast::<Node>().unwrap();
-                self.set_child_nodes_id(&node, id, view);
-                node
+        }
+    }
+
+    #[inline]
+    fn hydration_kind_matches(&self, parent: &D::Node, node: &D::Node, view: &RawView) -> bool {
+        let span = self.text_mode == TextMode::Span
+            && self.node_builder().children_namespace(parent).is_html();
+
+        match (self.document.node_kind(node), view) {
+            (Some(ref kind), &RawView::Text(_)) => span && kind == "span",
+            (None, &RawView::Text(_)) => !span && !self.document.is_comment(node),
+            (Some(ref kind), &RawView::Data { kind: ref view_kind, .. }) => {
+                kind == &view_kind.to_lowercase()
             }
+            _ => false,
         }
     }
 
+    /// the existing children of `node`, dropping the empty comments
+    /// `ToHtmlString` puts between adjacent text views
     #[inline]
-    fn set_child_nodes_id(&mut self, node: &Node, id: &String, view: &RawView) {
-        self.nodes_ids.insert(id.clone(), node.clone());
+    fn hydration_child_nodes(&self, node: &D::Node) -> Vec<D::Node> {
+        let mut child_nodes = Vec::new();
 
-        match view {
-            &RawView::Data { ref children, .. } => {
-                let mut index = 0;
-
-                for child_node in node.child_nodes().iter() {
-                    let child = &children[index];
-                    let child_id = view_id(id, child.key(), index);
-                    self.set_child_nodes_id(&child_node, &child_id, child);
-                    index += 1;
-                }
+        for child_node in self.document.child_nodes(node) {
+            if self.document.is_comment(&child_node) {
+                self.document.remove_child(node, &child_node);
+            } else {
+                child_nodes.push(child_node);
             }
-            _ => {}
         }
+
+        child_nodes
     }
 
+    /// the next existing node for `view`, whitespace-only text nodes are
+    /// never rendered for element views but are common in server markup
     #[inline]
-    fn remove_child_nodes_id(&mut self, id: &String, view: &RawView) {
-        let node_option = self.nodes_ids.remove_id(id);
+    fn next_hydration_node(
+        &self,
+        parent: &D::Node,
+        child_nodes: &mut IntoIter<D::Node>,
+        view: &RawView,
+    ) -> Option<D::Node> {
+        let is_text = match view {
+            &RawView::Text(_) => true,
+            _ => false,
+        };
 
-        if let Some(node) = node_option {
-            match view {
-                &RawView::Data { ref children, .. } => {
-                    let mut index = 0;
-
-                    for _child_node in node.child_nodes().iter() {
-                        let child = &children[index];
-                        let child_id = view_id(id, child.key(), index);
-                        self.remove_child_nodes_id(&child_id, child);
-                        index += 1;
-                    }
-                }
-                _ => {}
+        for child_node in child_nodes {
+            if !is_text && self.is_whitespace_text(&child_node) {
+                self.document.remove_child(parent, &child_node);
+            } else {
+                return Some(child_node);
             }
         }
+
+        None
+    }
+
+    #[inline]
+    fn hydrate_extra_nodes(
+        &self,
+        parent: &D::Node,
+        id: ViewId,
+        child_nodes: IntoIter<D::Node>,
+        mismatches: &mut Vec<HydrationMismatch>,
+    ) -> Result<(), HydrationMismatch> {
+        for child_node in child_nodes {
+            if !self.is_whitespace_text(&child_node) {
+                let actual = self.document.outer_html(&child_node);
+                let mismatch = HydrationMismatch::new(
+                    &self.nodes_ids.view_id_string(id),
+                    HydrationMismatchKind::ExtraChild,
+                    None,
+                    Some(actual),
+                );
+                self.hydration_mismatch(mismatches, mismatch)?;
+            }
+            self.document.remove_child(parent, &child_node);
+        }
+
+        Ok(())
+    }
+
+    #[inline]
+    fn is_whitespace_text(&self, node: &D::Node) -> bool {
+        self.document.node_kind(node).is_none()
+            && self.document.text_content(node).trim().is_empty()
+    }
+
+    /// forgets the ids of the subtree of `id`, returns how many it forgot
+    #[inline]
+    pub(crate) fn remove_child_nodes_id(&mut self, id: &str) -> usize {
+        match self.nodes_ids.id_handle(id) {
+            Some(handle) => remove_child_nodes_handle(&self.document, &self.nodes_ids, handle),
+            None => 0,
+        }
     }
 }
 
+impl<D: Dom> Drop for Patcher<D> {
+    #[inline]
+    fn drop(&mut self) {
+        self.teardown();
+    }
+}
+
+/// forgets the ids of the subtree of `handle`, walking its nodes, through
+/// portals, so no child id has to be rebuilt from its view
 #[inline]
-fn json_to_js_value(value: &Value) -> stdweb::Value {
-    match value {
-        &Value::Null => stdweb::Value::Null,
-        &Value::Bool(ref v) => stdweb::Value::Bool(*v),
-        &Value::Number(ref v) => stdweb::Value::Number(v.as_f64().unwrap().try_into().unwrap()),
-        &Value::String(ref v) => stdweb::Value::String(v.clone()),
-        &Value::Array(ref a) => {
-            let array: Vec<_> = a.iter().map(json_to_js_value).collect();
-            array.try_into().unwrap()
-        }
-        &Value::Object(ref o) => {
-            let object: HashMap<_, _> = o.iter()
-                .map(|(k, v)| (k.clone(), json_to_js_value(v)))
-                .collect();
-            object.try_into().unwrap()
+pub(crate) fn remove_child_nodes_handle<D: Dom>(
+    document: &D,
+    nodes_ids: &NodesIds<D>,
+    handle: NodeId,
+) -> usize {
+    let node = match nodes_ids.remove_handle(handle) {
+        Some(node) => node,
+        None => return 0,
+    };
+    let mut count = 1;
+
+    for child_node in document.child_nodes(&node) {
+        let child_node = nodes_ids.portal(&child_node).unwrap_or(child_node);
+
+        if let Some(child_handle) = nodes_ids.handle(&child_node) {
+            count += remove_child_nodes_handle(document, nodes_ids, child_handle);
         }
     }
+
+    count
+}
+
+/// takes the portals of the subtree of `node` out of their containers
+#[inline]
+pub(crate) fn detach_portals<D: Dom>(document: &D, nodes_ids: &NodesIds<D>, node: &D::Node) {
+    if !nodes_ids.has_portals() {
+        return;
+    }
+    if let Some(portal) = nodes_ids.remove_portal(node) {
+        if let Some(container) = document.parent_node(&portal) {
+            document.remove_child(&container, &portal);
+        }
+        detach_portals(document, nodes_ids, &portal);
+    }
+    for child_node in document.child_nodes(node) {
+        detach_portals(document, nodes_ids, &child_node);
+    }
+}
+
+#[inline]
+pub(crate) fn known_node<'a, N>(id: &str, node: Option<&'a N>) -> Result<&'a N, PatchError> {
+    node.ok_or_else(|| PatchError::UnknownId(id.into()))
+}
+
+#[inline]
+fn expect_patched<T>(result: Result<T, PatchError>) {
+    if let Err(error) = result {
+        panic!("{}", error);
+    }
+}
+
+#[test]
+fn test_patcher_memory_document() {
+    use super::tests::{memory_patcher, props};
+
+    let (_, root, mut patcher) = memory_patcher();
+    let id = "0".to_string();
+    let mut report = PatchReport::new();
+
+    let view = view! {
+        <ul class="List">
+            <li>{"a"}</li>
+            <li>{"b"}</li>
+        </ul>
+    };
+    patcher
+        .apply_patch(&id, &Patch::Mount((&view).into()), &mut report)
+        .unwrap();
+    assert_eq!(report.created, 5);
+    assert_eq!(report.attributes_set, 1);
+
+    assert_eq!(root.inner_html(), "<ul class=\"List\"><li>a</li><li>b</li></ul>");
+    let list = root.child(0).unwrap();
+    let text = list.child(1).unwrap().child(0).unwrap();
+    assert_eq!(patcher.id(&list), Some(id.clone()));
+    assert!(text.is_text());
+    assert!(patcher.id(&text).is_some());
+
+    let prev_props = props(json!({ "class": "List" }));
+    let diff_props = props(json!({ "class": null, "style": { "color": "red" } }));
+    patcher
+        .apply_patch(&id, &Patch::Props(prev_props, diff_props), &mut report)
+        .unwrap();
+
+    assert_eq!(list.attribute("class"), None);
+    assert_eq!(list.style("color"), Some("red".to_string()));
+    assert_eq!(report.attributes_removed, 1);
+    assert_eq!(report.styles_set, 1);
+}
+
+#[test]
+fn test_patcher_hydrate() {
+    use super::ToHtmlString;
+    use super::tests::memory_patcher;
+
+    let (document, root, mut patcher) = memory_patcher();
+    let view = view! {
+        <ul class="List">
+            <li>{"a"}</li>
+            <li>{"b"}</li>
+        </ul>
+    };
+    let raw_view: RawView = (&view).into();
+
+    document.set_inner_html(&root, &raw_view.to_html_string());
+
+    let list = root.child(0).unwrap();
+    let item = list.child(1).unwrap();
+    item.child(0).unwrap().set_text_content("c");
+    list.set_attribute("data-stale", "true");
+
+    let id = "0".to_string();
+    let mut mismatches = Vec::new();
+    patcher
+        .hydrate_node(
+            &root.clone(),
+            Some(&list),
+            ViewId::Id(&id),
+            &raw_view,
+            &mut mismatches,
+            &mut PatchReport::new(),
+        )
+        .unwrap();
+
+    assert_eq!(root.child(0), Some(list.clone()));
+    assert_eq!(list.child(1), Some(item));
+    assert_eq!(root.inner_html(), "<ul class=\"List\"><li>a</li><li>b</li></ul>");
+    assert_eq!(patcher.id(&list), Some(id.clone()));
+
+    let kinds: Vec<_> = mismatches.iter().map(|m| m.kind.clone()).collect();
+    assert_eq!(
+        kinds,
+        vec![
+            HydrationMismatchKind::Attribute("data-stale".into()),
+            HydrationMismatchKind::Text,
+        ]
+    );
+    assert_eq!(mismatches[0].id, id);
+    assert_eq!(document.warnings().len(), 2);
+
+    list.set_attribute("class", "Other");
+    patcher.set_hydration_policy(HydrationPolicy::Fail);
+    let mismatch = patcher
+        .hydrate_node(
+            &root.clone(),
+            Some(&list),
+            ViewId::Id(&id),
+            &raw_view,
+            &mut Vec::new(),
+            &mut PatchReport::new(),
+        )
+        .unwrap_err();
+    assert_eq!(mismatch.kind, HydrationMismatchKind::Attribute("class".into()));
+}
+
+#[test]
+fn test_patcher_hydrate_transaction() {
+    use super::ToHtmlString;
+    use super::tests::{memory_patcher, props};
+
+    let (document, root, mut patcher) = memory_patcher();
+    let view: RawView = (&view! {
+        <ul class="List">
+            <li>{"a"}</li>
+            <li>{"b"}</li>
+        </ul>
+    }).into();
+    let html = view.to_html_string();
+    document.set_inner_html(&root, &html);
+    let list = root.child(0).unwrap();
+
+    let mut transaction = Transaction::new();
+    transaction.mount("0", view.clone());
+    assert!(patcher.hydrate(&transaction).unwrap().is_empty());
+    assert_eq!(root.inner_html(), html);
+    assert_eq!(patcher.node("0"), Some(list.clone()));
+    assert!(patcher.id(&list.child(1).unwrap()).is_some());
+
+    let mut stale = Transaction::new();
+    stale.mount("0", view.clone());
+    stale.props("missing", Map::new(), props(json!({ "class": "a" })));
+
+    let (document, root, mut patcher) = memory_patcher();
+    document.set_inner_html(&root, &html);
+    match patcher.hydrate(&stale) {
+        Err(HydrationError::Patch(error)) => {
+            assert_eq!(error, PatchError::UnknownId("missing".to_string()))
+        }
+        result => panic!("expected a patch error, got {:?}", result),
+    }
+
+    let (document, root, mut patcher) = memory_patcher();
+    document.set_inner_html(&root, &html);
+    patcher.set_error_policy(PatchErrorPolicy::Continue);
+    assert!(patcher.hydrate(&stale).unwrap().is_empty());
+    assert_eq!(root.inner_html(), html);
+    assert_eq!(
+        patcher.last_report().errors,
+        vec![PatchError::UnknownId("missing".to_string())]
+    );
+}
+
+#[test]
+fn test_patcher_svg_namespace() {
+    use super::{HTML_NAMESPACE, SVG_NAMESPACE};
+    use super::tests::{memory_patcher, props};
+
+    let (_, root, mut patcher) = memory_patcher();
+    let view = view! {
+        <svg viewBox="0 0 10 10">
+            <circle r=4/>
+            <foreignObject><p>{"html"}</p></foreignObject>
+        </svg>
+    };
+    patcher
+        .apply_patch(
+            &"0".to_string(),
+            &Patch::Mount((&view).into()),
+            &mut PatchReport::new(),
+        )
+        .unwrap();
+
+    let svg = root.child(0).unwrap();
+    let circle = svg.child(0).unwrap();
+    let paragraph = svg.child(1).unwrap().child(0).unwrap();
+    assert_eq!(svg.namespace_uri(), Some(SVG_NAMESPACE.to_string()));
+    assert_eq!(circle.namespace_uri(), Some(SVG_NAMESPACE.to_string()));
+    assert_eq!(paragraph.namespace_uri(), Some(HTML_NAMESPACE.to_string()));
+    assert_eq!(svg.attribute("viewBox"), Some("0 0 10 10".to_string()));
+
+    let circle_id = patcher.id(&circle).unwrap();
+    let prev_props = props(json!({ "r": 4 }));
+    let diff_props = props(json!({ "xlink:href": "#dot" }));
+    patcher
+        .apply_patch(
+            &circle_id,
+            &Patch::Props(prev_props, diff_props),
+            &mut PatchReport::new(),
+        )
+        .unwrap();
+    assert_eq!(circle.attribute("xlink:href"), Some("#dot".to_string()));
+}
+
+#[test]
+fn test_patcher_text_mode() {
+    use super::ToHtmlString;
+    use super::tests::memory_patcher;
+
+    let view = view! {
+        <p>{"a"}{"b"}</p>
+    };
+    let raw_view: RawView = (&view).into();
+    let id = "0".to_string();
+
+    let (document, root, mut patcher) = memory_patcher();
+    document.set_inner_html(&root, &raw_view.to_html_string());
+    let mut mismatches = Vec::new();
+    let paragraph = root.child(0).unwrap();
+    patcher
+        .hydrate_node(
+            &root.clone(),
+            Some(&paragraph),
+            ViewId::Id(&id),
+            &raw_view,
+            &mut mismatches,
+            &mut PatchReport::new(),
+        )
+        .unwrap();
+    assert!(mismatches.is_empty());
+    assert_eq!(root.inner_html(), "<p>ab</p>");
+    assert_eq!(paragraph.children().len(), 2);
+
+    let (_, root, mut patcher) = memory_patcher();
+    patcher.set_text_mode(TextMode::Span);
+    patcher
+        .apply_patch(&id, &Patch::Mount(raw_view), &mut PatchReport::new())
+        .unwrap();
+    assert_eq!(root.inner_html(), "<p><span>a</span><span>b</span></p>");
+}
+
+#[test]
+fn test_patcher_try_patch() {
+    use super::tests::{memory_patcher, props};
+
+    let (_, root, mut patcher) = memory_patcher();
+    let id = "0".to_string();
+    let view = view! { <p/> };
+
+    let mut batch = Batch::new();
+    batch.push_patch(&id, &Patch::Mount((&view).into()));
+    batch.push_patch(
+        &"missing".to_string(),
+        &Patch::Props(Map::new(), props(json!({ "a": 1 }))),
+    );
+    batch.push_patch(
+        &id,
+        &Patch::Props(Map::new(), props(json!({ "a b": 1, "c": 2 }))),
+    );
+
+    assert_eq!(
+        patcher.try_patch_batch(&batch).unwrap_err(),
+        PatchError::UnknownId("missing".to_string())
+    );
+    assert_eq!(root.inner_html(), "<p></p>");
+
+    let (document, root, mut patcher) = memory_patcher();
+    patcher.set_error_policy(PatchErrorPolicy::Continue);
+
+    let report = patcher.try_patch_batch(&batch).unwrap();
+    assert_eq!(report.errors.len(), 2);
+    assert_eq!(report.errors[0], PatchError::UnknownId("missing".to_string()));
+    match report.errors[1] {
+        PatchError::JsException { ref id, .. } => assert_eq!(id, "0"),
+        ref error => panic!("unexpected error {}", error),
+    }
+    assert_eq!(root.inner_html(), "<p c=\"2\"></p>");
+    assert_eq!(document.warnings().len(), 2);
+}
+
+#[test]
+fn test_patcher_try_patch_errors() {
+    use virtual_view::Order;
+
+    use super::tests::{memory_patcher, props};
+
+    let (document, root, mut patcher) = memory_patcher();
+    let list: RawView = (&view! { <ul><li>{"a"}</li></ul> }).into();
+    let mut transaction = Transaction::new();
+    transaction.mount("0", list.clone());
+    patcher.try_patch(&transaction).unwrap();
+
+    let mut transaction = Transaction::new();
+    transaction.props("missing", Map::new(), props(json!({ "a": 1 })));
+    assert_eq!(
+        patcher.try_patch(&transaction).unwrap_err(),
+        PatchError::UnknownId("missing".to_string())
+    );
+
+    let list_node = root.child(0).unwrap();
+    document.remove_child(&root, &list_node);
+    let mut transaction = Transaction::new();
+    transaction.replace("0", list, (&view! { <p/> }).into());
+    assert_eq!(
+        patcher.try_patch(&transaction).unwrap_err(),
+        PatchError::MissingParent("0".to_string())
+    );
+    document.append_child(&root, &list_node);
+
+    let mut transaction = Transaction::new();
+    transaction.order("0", Order::new(vec![(3, None)], Vec::new()));
+    assert_eq!(
+        patcher.try_patch(&transaction).unwrap_err(),
+        PatchError::OrderIndexOutOfRange {
+            id: "0".to_string(),
+            index: 3,
+            len: 1,
+        }
+    );
+    assert_eq!(root.inner_html(), "<ul><li>a</li></ul>");
+
+    patcher.set_error_policy(PatchErrorPolicy::Continue);
+    let warnings = document.warnings().len();
+    let mut transaction = Transaction::new();
+    transaction.props("missing", Map::new(), props(json!({ "a": 1 })));
+    transaction.props("0", Map::new(), props(json!({ "class": "List" })));
+
+    let report = patcher.try_patch(&transaction).unwrap();
+    assert_eq!(report.errors, vec![PatchError::UnknownId("missing".to_string())]);
+    assert_eq!(patcher.last_report().errors, report.errors);
+    assert_eq!(document.warnings().len(), warnings + 1);
+    assert_eq!(root.inner_html(), "<ul class=\"List\"><li>a</li></ul>");
+}
+
+#[test]
+fn test_patcher_rollback() {
+    use std::cell::RefCell;
+    use std::rc::Rc;
+
+    use super::tests::{memory_patcher, props};
+
+    let (document, root, mut patcher) = memory_patcher();
+    let changes = Rc::new(RefCell::new(Vec::new()));
+    let id = "0".to_string();
+    let item_id = "0.item".to_string();
+    let list: RawView =
+        (&view! { <ul class="a"><li ref="first">{"a"}</li><li>{"b"}</li></ul> }).into();
+    let item: RawView = (&view! { <li>{"c"}</li> }).into();
+    let first: RawView = (&view! { <li ref="first">{"a"}</li> }).into();
+    let replaced: RawView = (&view! { <p/> }).into();
+
+    let mut batch = Batch::new();
+    batch.push_patch(&id, &Patch::Mount(list));
+    patcher.try_patch_batch(&batch).unwrap();
+    let html = root.inner_html();
+    // a class added from outside the patcher, which only touches its own
+    let list_node = root.child(0).unwrap();
+    document.add_class(&list_node, "outside");
+    let first_node = root.child(0).unwrap().child(0).unwrap();
+    let first_id = patcher.id(&first_node).unwrap();
+
+    let mut batch = Batch::new();
+    batch.push_patch(
+        &id,
+        &Patch::Props(props(json!({ "class": "a" })), props(json!({ "class": "b" }))),
+    );
+    batch.push_patch(&id, &Patch::Insert(item_id.clone(), 1, item));
+    batch.push_patch(&first_id, &Patch::Replace(first, replaced));
+    batch.push_patch(&"missing".to_string(), &Patch::Props(Map::new(), Map::new()));
+
+    let listener_changes = changes.clone();
+    patcher.set_ref_listener(Some(Box::new(move |change: &RefChange| {
+        listener_changes.borrow_mut().push(change.clone());
+    })));
+
+    patcher.set_error_policy(PatchErrorPolicy::Rollback);
+    assert_eq!(
+        patcher.try_patch_batch(&batch).unwrap_err(),
+        PatchError::UnknownId("missing".to_string())
+    );
+    assert_eq!(list_node.attribute("class"), Some("outside a".into()));
+    document.remove_class(&list_node, "outside");
+    assert_eq!(root.inner_html(), html);
+    assert!(patcher.node(&item_id).is_none());
+    assert_eq!(patcher.id(&first_node), Some(first_id.clone()));
+    assert!(patcher.id(&first_node.child(0).unwrap()).is_some());
+    assert_eq!(patcher.ref_node("first"), Some(first_node.clone()));
+
+    patcher.try_patch_batch(&Batch::new()).unwrap();
+    assert!(changes.borrow().is_empty());
+}
+
+#[test]
+fn test_patcher_portal() {
+    use super::tests::memory_patcher;
+
+    let (document, root, mut patcher) = memory_patcher();
+    let body = document.create_element("body");
+    let modal = document.create_element("div");
+    document.set_attribute(&modal, "id", "modal");
+    document.append_child(&document.document_node(), &body);
+    document.append_child(&body, &root);
+    document.append_child(&body, &modal);
+
+    let id = "0".to_string();
+    let view: RawView = (&view! {
+        <div>
+            <p portal="modal">{"a"}</p>
+            <span portal="body"/>
+        </div>
+    }).into();
+
+    let mut batch = Batch::new();
+    batch.push_patch(&id, &Patch::Mount(view.clone()));
+    batch.push_event(&id, &"onclick".to_string(), true);
+    let report = patcher.try_patch_batch(&batch).unwrap();
+    // the root and both containers
+    assert_eq!(report.listeners_added, 3);
+    assert_eq!(root.inner_html(), "<div></div>");
+    assert_eq!(modal.inner_html(), "<p>a</p>");
+    assert_eq!(body.children().len(), 3);
+
+    let item = modal.child(0).unwrap();
+    assert!(patcher.id(&item).is_some());
+    assert!(patcher.id(&item.child(0).unwrap()).is_some());
+
+    let mut batch = Batch::new();
+    batch.push_remove(&id, &view);
+    let report = patcher.try_patch_batch(&batch).unwrap();
+    assert_eq!(report.listeners_removed, 2);
+    assert_eq!(patcher.events.roots(), vec![root.clone()]);
+    assert_eq!(root.inner_html(), "");
+    assert_eq!(modal.inner_html(), "");
+    assert_eq!(body.children().len(), 2);
+    assert!(patcher.id(&item).is_none());
+}
+
+#[test]
+fn test_patcher_boolean_attributes() {
+    use super::tests::{memory_patcher, props};
+
+    let (_, root, mut patcher) = memory_patcher();
+    let id = "0".to_string();
+
+    let mut batch = Batch::new();
+    batch.push_patch(&id, &Patch::Mount((&view! { <button/> }).into()));
+    batch.push_patch(
+        &id,
+        &Patch::Props(Map::new(), props(json!({ "disabled": true, "htmlFor": "a" }))),
+    );
+    patcher.try_patch_batch(&batch).unwrap();
+    assert_eq!(root.inner_html(), "<button disabled=\"\" for=\"a\"></button>");
+
+    let mut batch = Batch::new();
+    batch.push_patch(
+        &id,
+        &Patch::Props(
+            props(json!({ "disabled": true, "htmlFor": "a" })),
+            props(json!({ "disabled": false, "htmlFor": null })),
+        ),
+    );
+    patcher.try_patch_batch(&batch).unwrap();
+    assert_eq!(root.inner_html(), "<button></button>");
+}
+
+#[test]
+fn test_patcher_class_list() {
+    use super::ToHtmlString;
+    use super::tests::{memory_patcher, props};
+
+    let (document, root, mut patcher) = memory_patcher();
+    let id = "0".to_string();
+    let view: RawView = (&view! { <p class={["a", "b a"]}/> }).into();
+
+    let mut batch = Batch::new();
+    batch.push_patch(&id, &Patch::Mount(view.clone()));
+    patcher.try_patch_batch(&batch).unwrap();
+    assert_eq!(root.inner_html(), view.to_html_string());
+    assert_eq!(root.inner_html(), "<p class=\"a b\"></p>");
+
+    // classes added by other code are kept
+    let node = patcher.node(&id).unwrap();
+    document.add_class(&node, "x");
+
+    let mut batch = Batch::new();
+    batch.push_patch(
+        &id,
+        &Patch::Props(
+            props(json!({ "class": ["a", "b a"] })),
+            props(json!({ "class": { "a": true, "b": false, "c": true } })),
+        ),
+    );
+    let report = patcher.try_patch_batch(&batch).unwrap();
+    assert_eq!(node.attribute("class"), Some("a x c".into()));
+    assert_eq!((report.classes_added, report.classes_removed), (1, 1));
+}
+
+#[test]
+fn test_patcher_style() {
+    use super::ToHtmlString;
+    use super::tests::{memory_patcher, props};
+
+    let (_, root, mut patcher) = memory_patcher();
+    let id = "0".to_string();
+    let view: RawView = (&view! { <p style={{ "width": 10, "zIndex": 2 }}/> }).into();
+
+    let mut batch = Batch::new();
+    batch.push_patch(&id, &Patch::Mount(view.clone()));
+    patcher.try_patch_batch(&batch).unwrap();
+    assert_eq!(view.to_html_string(), "<p style=\"width:10px;z-index:2;\"></p>");
+
+    let node = patcher.node(&id).unwrap();
+    assert_eq!(node.style("width"), Some("10px".into()));
+    assert_eq!(node.style("z-index"), Some("2".into()));
+
+    let mut batch = Batch::new();
+    batch.push_patch(
+        &id,
+        &Patch::Props(
+            props(json!({ "style": { "width": 10, "zIndex": 2 } })),
+            props(json!({ "style": { "zIndex": 2, "--gap": "4px", "color": "red !important" } })),
+        ),
+    );
+    let report = patcher.try_patch_batch(&batch).unwrap();
+    assert_eq!(node.style("width"), None);
+    assert_eq!(node.style("z-index"), Some("2".into()));
+    assert_eq!(node.style("--gap"), Some("4px".into()));
+    assert_eq!(node.style("color"), Some("red !important".into()));
+    assert_eq!((report.styles_set, report.styles_removed), (2, 1));
+}
+
+#[test]
+fn test_patcher_raw_html() {
+    use super::ToHtmlString;
+    use super::tests::{memory_patcher, props};
+
+    let (_, root, mut patcher) = memory_patcher();
+    let id = "0".to_string();
+    let view: RawView = (&view! {
+        <div dangerouslySetInnerHTML={{ "__html": "<b>a</b> c" }}>{"ignored"}</div>
+    }).into();
+
+    let mut batch = Batch::new();
+    batch.push_patch(&id, &Patch::Mount(view.clone()));
+    patcher.try_patch_batch(&batch).unwrap();
+    assert_eq!(root.inner_html(), "<div><b>a</b> c</div>");
+    assert_eq!(root.inner_html(), view.to_html_string());
+
+    let node = patcher.node(&id).unwrap();
+    assert_eq!(patcher.id(&node.child(0).unwrap()), None);
+
+    let mut batch = Batch::new();
+    batch.push_patch(&id, &Patch::Insert("0.1".into(), 0, (&view! { <p/> }).into()));
+    assert_eq!(
+        patcher.try_patch_batch(&batch).unwrap_err(),
+        PatchError::OpaqueNode(id.clone())
+    );
+
+    let mut batch = Batch::new();
+    batch.push_patch(
+        &id,
+        &Patch::Props(
+            props(json!({ "dangerouslySetInnerHTML": { "__html": "<b>a</b> c" } })),
+            props(json!({ "dangerouslySetInnerHTML": null })),
+        ),
+    );
+    patcher.try_patch_batch(&batch).unwrap();
+    assert_eq!(root.inner_html(), "<div></div>");
+
+    let mut batch = Batch::new();
+    batch.push_patch(&id, &Patch::Insert("0.1".into(), 0, (&view! { <p/> }).into()));
+    batch.push_patch(
+        &id,
+        &Patch::Props(
+            Map::new(),
+            props(json!({ "dangerouslySetInnerHTML": { "__html": "<i>b</i>" } })),
+        ),
+    );
+    patcher.try_patch_batch(&batch).unwrap();
+    assert_eq!(root.inner_html(), "<div><i>b</i></div>");
+    assert!(patcher.node("0.1").is_none());
+}
+
+#[test]
+fn test_patcher_raw_html_rollback() {
+    use super::tests::{memory_patcher, props};
+
+    let (_, root, mut patcher) = memory_patcher();
+    let id = "0".to_string();
+    let view: RawView = (&view! { <div><p ref="first">{"a"}</p><span/></div> }).into();
+
+    let mut batch = Batch::new();
+    batch.push_patch(&id, &Patch::Mount(view));
+    patcher.try_patch_batch(&batch).unwrap();
+    let html = root.inner_html();
+    let node = patcher.node(&id).unwrap();
+    let first_node = patcher.node("0.0").unwrap();
+
+    patcher.set_error_policy(PatchErrorPolicy::Rollback);
+    let mut batch = Batch::new();
+    batch.push_patch(
+        &id,
+        &Patch::Props(
+            Map::new(),
+            props(json!({ "dangerouslySetInnerHTML": { "__html": "<i>b</i>" } })),
+        ),
+    );
+    batch.push_patch(
+        &"missing".to_string(),
+        &Patch::Props(Map::new(), props(json!({ "a": 1 }))),
+    );
+    assert_eq!(
+        patcher.try_patch_batch(&batch).unwrap_err(),
+        PatchError::UnknownId("missing".to_string())
+    );
+
+    assert_eq!(root.inner_html(), html);
+    assert!(!patcher.nodes_ids().is_opaque(&node));
+    assert_eq!(patcher.node("0.0"), Some(first_node.clone()));
+    assert_eq!(patcher.ref_node("first"), Some(first_node));
+    assert!(patcher.node("0.1").is_some());
+}
+
+#[test]
+fn test_patcher_refs() {
+    use std::cell::RefCell;
+    use std::rc::Rc;
+
+    use super::RefChangeKind;
+    use super::tests::{memory_patcher, props};
+
+    let (_, root, mut patcher) = memory_patcher();
+    let changes = Rc::new(RefCell::new(Vec::new()));
+    let id = "0".to_string();
+    let input: RawView = (&view! { <input ref="name"/> }).into();
+    let replaced: RawView = (&view! { <textarea ref="name"/> }).into();
+
+    let listener_changes = changes.clone();
+    patcher.set_ref_listener(Some(Box::new(move |change: &RefChange| {
+        listener_changes
+            .borrow_mut()
+            .push((change.name.clone(), change.id.clone(), change.kind));
+    })));
+
+    let mut batch = Batch::new();
+    batch.push_patch(&id, &Patch::Mount((&view! { <div/> }).into()));
+    batch.push_patch(&id, &Patch::Insert("0.input".into(), 0, input.clone()));
+    patcher.try_patch_batch(&batch).unwrap();
+
+    let node = root.child(0).unwrap().child(0).unwrap();
+    assert_eq!(node.attribute("ref"), None);
+    assert_eq!(patcher.ref_node("name"), Some(node.clone()));
+    assert_eq!(patcher.ref_id("name"), Some("0.input".into()));
+
+    let mut batch = Batch::new();
+    batch.push_patch(&"0.input".into(), &Patch::Replace(input, replaced));
+    patcher.try_patch_batch(&batch).unwrap();
+    assert_ne!(patcher.ref_node("name"), Some(node));
+    assert_eq!(patcher.ref_id("name"), Some("0.input".into()));
+
+    let mut batch = Batch::new();
+    batch.push_patch(
+        &"0.input".into(),
+        &Patch::Props(props(json!({ "ref": "name" })), props(json!({ "ref": null }))),
+    );
+    patcher.try_patch_batch(&batch).unwrap();
+    assert_eq!(patcher.ref_node("name"), None);
+
+    let name = "name".to_string();
+    let input_id = "0.input".to_string();
+    assert_eq!(
+        *changes.borrow(),
+        vec![
+            (name.clone(), input_id.clone(), RefChangeKind::Mounted),
+            (name.clone(), input_id.clone(), RefChangeKind::Replaced),
+            (name, input_id, RefChangeKind::Removed),
+        ]
+    );
+}
+
+#[test]
+fn test_patcher_controlled_input() {
+    use super::tests::{memory_patcher, props};
+
+    let (document, root, mut patcher) = memory_patcher();
+    document.append_child(&document.document_node(), &root);
+    let id = "0".to_string();
+
+    let mut batch = Batch::new();
+    batch.push_patch(&id, &Patch::Mount((&view! { <input/> }).into()));
+    batch.push_patch(
+        &id,
+        &Patch::Props(Map::new(), props(json!({ "value": 1, "checked": true }))),
+    );
+    batch.push_event(&id, &"oninput".to_string(), true);
+    patcher.try_patch_batch(&batch).unwrap();
+
+    let input = patcher.node(&id).unwrap();
+    assert_eq!(input.attribute("value"), None);
+    assert_eq!(input.property("value"), Some(json!("1")));
+    assert_eq!(input.property("checked"), Some(json!(true)));
+
+    // the user types, nothing sets the value, it goes back on the next frame
+    input.set_property("value", &json!("12"));
+    document.dispatch_event(&input, "input", Prop::Null);
+    assert_eq!(document.run_animation_frames(), 1);
+    assert_eq!(input.property("value"), Some(json!("1")));
+
+    let mut batch = Batch::new();
+    batch.push_patch(
+        &id,
+        &Patch::Props(props(json!({ "checked": true })), props(json!({ "checked": null }))),
+    );
+    patcher.try_patch_batch(&batch).unwrap();
+    assert_eq!(input.property("checked"), Some(json!(false)));
+}
+
+#[test]
+fn test_patcher_controlled_without_handlers() {
+    use super::tests::{memory_patcher, props};
+
+    let (document, root, mut patcher) = memory_patcher();
+    document.append_child(&document.document_node(), &root);
+    let id = "0".to_string();
+
+    let mut batch = Batch::new();
+    batch.push_patch(&id, &Patch::Mount((&view! { <input/> }).into()));
+    let report = patcher.try_patch_batch(&batch).unwrap();
+    assert_eq!(report.listeners_added, 0);
+
+    // no view handles input or change, the value is still put back
+    let mut batch = Batch::new();
+    batch.push_patch(&id, &Patch::Props(Map::new(), props(json!({ "value": "a" }))));
+    let report = patcher.try_patch_batch(&batch).unwrap();
+    assert_eq!(report.listeners_added, 2);
+
+    let input = patcher.node(&id).unwrap();
+    input.set_property("value", &json!("ab"));
+    document.dispatch_event(&input, "change", Prop::Null);
+    assert_eq!(document.run_animation_frames(), 1);
+    assert_eq!(input.property("value"), Some(json!("a")));
+
+    let report = patcher.try_patch_batch(&batch).unwrap();
+    assert_eq!(report.listeners_added, 0);
+}
+
+#[test]
+fn test_patcher_scoped_events() {
+    use super::{MemoryDocument, Record, Recorder};
+
+    let document = MemoryDocument::new();
+    let body = document.create_element("body");
+    document.append_child(&document.document_node(), &body);
+
+    let mut patchers = Vec::new();
+    let mut recorders = Vec::new();
+    let id = "0".to_string();
+
+    for _ in 0..2 {
+        let root = document.create_element("div");
+        document.append_child(&body, &root);
+
+        let mut patcher = Patcher::new(root, document.clone(), EventManager::new());
+        let recorder = Recorder::new();
+        let mut batch = Batch::new();
+        batch.push_patch(&id, &Patch::Mount((&view! { <button/> }).into()));
+        batch.push_event(&id, &"onclick".to_string(), true);
+
+        let report = patcher.try_patch_batch(&batch).unwrap();
+        assert_eq!(report.listeners_added, 1);
+        patcher.set_recorder(Some(recorder.clone()));
+        patchers.push(patcher);
+        recorders.push(recorder);
+    }
+    assert_eq!(document.listener_count(), 2);
+
+    let button = patchers[0].node(&id).unwrap();
+    document.dispatch_event(&button, "click", Prop::Null);
+    document.dispatch_event(&body, "click", Prop::Null);
+
+    match recorders[0].recording().records() {
+        &[Record::Event { ref id, .. }] => assert_eq!(id, "0"),
+        records => panic!("unexpected records {:?}", records),
+    }
+    assert!(recorders[1].recording().records().is_empty());
+
+    assert_eq!(patchers[1].teardown(), 1);
+    assert_eq!(document.listener_count(), 1);
+    patchers.clear();
+    assert_eq!(document.listener_count(), 0);
+}
+
+#[test]
+fn test_patcher_shadow_root() {
+    use super::{MemoryDocument, Record, Recorder};
+
+    let document = MemoryDocument::new();
+    let host = document.create_element("div");
+    document.append_child(&document.document_node(), &host);
+
+    let mut patcher =
+        Patcher::with_shadow_root(&host, document.clone(), EventManager::new()).unwrap();
+    let recorder = Recorder::new();
+    let id = "0".to_string();
+    let mut batch = Batch::new();
+    batch.push_patch(&id, &Patch::Mount((&view! { <button/> }).into()));
+    batch.push_event(&id, &"onclick".to_string(), true);
+    patcher.try_patch_batch(&batch).unwrap();
+    patcher.set_recorder(Some(recorder.clone()));
+
+    let shadow_root = host.shadow_root().unwrap();
+    assert_eq!(patcher.root(), &shadow_root);
+    assert_eq!(host.inner_html(), "");
+    assert_eq!(shadow_root.inner_html(), "<button></button>");
+
+    // a node inside the button's own shadow tree still resolves to the button
+    let button = patcher.node(&id).unwrap();
+    let label = document.create_element("span");
+    document.append_child(&document.attach_shadow(&button).unwrap(), &label);
+    document.dispatch_event(&label, "click", Prop::Null);
+
+    match recorder.recording().records() {
+        &[Record::Event { ref id, .. }] => assert_eq!(id, "0"),
+        records => panic!("unexpected records {:?}", records),
+    }
 }
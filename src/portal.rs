@@ -0,0 +1,34 @@
+use serde_json::Value;
+use virtual_view::RawView;
+
+use super::Dom;
+
+/// the prop that makes a view a portal, its node is mounted into the
+/// container named by the prop instead of its parent, which only holds an
+/// empty text node in its place, `body` is the document's body and any other
+/// string the id of an element
+///
+/// a portal is only set up when its node is created, changing the prop later
+/// does not move the node
+pub const PORTAL_PROP: &'static str = "portal";
+
+/// the container named by `view`'s portal prop, if it is a portal
+#[inline]
+pub fn portal_target(view: &RawView) -> Option<&str> {
+    match view {
+        &RawView::Data { ref props, .. } => match props.get(PORTAL_PROP) {
+            Some(&Value::String(ref target)) => Some(target),
+            _ => None,
+        },
+        &RawView::Text(_) => None,
+    }
+}
+
+#[inline]
+pub fn portal_container<D: Dom>(document: &D, target: &str) -> Option<D::Node> {
+    if target == "body" {
+        document.body()
+    } else {
+        document.element_by_id(target)
+    }
+}
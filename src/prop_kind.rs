@@ -0,0 +1,300 @@
+use serde_json::{Map, Value};
+
+use super::class_list::{class_string, is_class};
+use super::portal::PORTAL_PROP;
+use super::raw_html::RAW_HTML_PROP;
+use super::refs::REF_PROP;
+use super::style::style_string;
+use super::utils::prop_to_html_string;
+
+/// how a prop is applied to an element, shared by the `Patcher` and
+/// `ToHtmlString` so a patched node and its rendered html always agree
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PropKind {
+    /// an attribute holding the prop as a string, the dom reflects it to the
+    /// matching property, removed with `removeAttribute`
+    Attribute,
+    /// an attribute that is present and empty or absent, `false` and `null`
+    /// remove it so `disabled={false}` leaves a button enabled
+    BooleanAttribute,
+    /// a dom property with no attribute, left out of html and set back to its
+    /// default when removed
+    Property,
+}
+
+/// boolean attributes by element kind, `*` for every element
+const BOOLEAN_ATTRIBUTES: &'static [(&'static str, &'static str)] = &[
+    ("*", "autofocus"),
+    ("*", "hidden"),
+    ("*", "inert"),
+    ("*", "itemscope"),
+    ("audio", "autoplay"),
+    ("audio", "controls"),
+    ("audio", "loop"),
+    ("audio", "muted"),
+    ("button", "disabled"),
+    ("button", "formnovalidate"),
+    ("details", "open"),
+    ("dialog", "open"),
+    ("fieldset", "disabled"),
+    ("form", "novalidate"),
+    ("iframe", "allowfullscreen"),
+    ("img", "ismap"),
+    ("input", "checked"),
+    ("input", "disabled"),
+    ("input", "formnovalidate"),
+    ("input", "multiple"),
+    ("input", "readonly"),
+    ("input", "required"),
+    ("ol", "reversed"),
+    ("optgroup", "disabled"),
+    ("option", "disabled"),
+    ("option", "selected"),
+    ("script", "async"),
+    ("script", "defer"),
+    ("script", "nomodule"),
+    ("select", "disabled"),
+    ("select", "multiple"),
+    ("select", "required"),
+    ("textarea", "disabled"),
+    ("textarea", "readonly"),
+    ("textarea", "required"),
+    ("track", "default"),
+    ("video", "autoplay"),
+    ("video", "controls"),
+    ("video", "loop"),
+    ("video", "muted"),
+    ("video", "playsinline"),
+];
+
+#[derive(Clone, Copy)]
+enum PropertyDefault {
+    False,
+    Zero,
+    One,
+}
+
+/// properties without an attribute by element kind, and the value they are
+/// set back to when removed
+const PROPERTIES: &'static [(&'static str, &'static str, PropertyDefault)] = &[
+    ("*", "scrollLeft", PropertyDefault::Zero),
+    ("*", "scrollTop", PropertyDefault::Zero),
+    ("audio", "currentTime", PropertyDefault::Zero),
+    ("audio", "playbackRate", PropertyDefault::One),
+    ("audio", "volume", PropertyDefault::One),
+    ("input", "indeterminate", PropertyDefault::False),
+    ("video", "currentTime", PropertyDefault::Zero),
+    ("video", "playbackRate", PropertyDefault::One),
+    ("video", "volume", PropertyDefault::One),
+];
+
+/// dom property names accepted for the attribute they reflect
+const ALIASES: &'static [(&'static str, &'static str)] = &[
+    ("acceptCharset", "accept-charset"),
+    ("allowFullScreen", "allowfullscreen"),
+    ("autoFocus", "autofocus"),
+    ("autoPlay", "autoplay"),
+    ("className", "class"),
+    ("colSpan", "colspan"),
+    ("contentEditable", "contenteditable"),
+    ("crossOrigin", "crossorigin"),
+    ("formNoValidate", "formnovalidate"),
+    ("htmlFor", "for"),
+    ("httpEquiv", "http-equiv"),
+    ("isMap", "ismap"),
+    ("maxLength", "maxlength"),
+    ("minLength", "minlength"),
+    ("noModule", "nomodule"),
+    ("noValidate", "novalidate"),
+    ("playsInline", "playsinline"),
+    ("readOnly", "readonly"),
+    ("rowSpan", "rowspan"),
+    ("tabIndex", "tabindex"),
+];
+
+/// the attribute `key` sets, `className` is `class`
+#[inline]
+pub fn attribute_name(key: &str) -> &str {
+    ALIASES
+        .iter()
+        .find(|&&(alias, _)| alias == key)
+        .map_or(key, |&(_, name)| name)
+}
+
+/// whether `key` means something different on some kinds of elements, so
+/// `prop_kind` needs the element's kind for it
+#[inline]
+pub fn is_element_prop(key: &str) -> bool {
+    let name = attribute_name(key);
+
+    BOOLEAN_ATTRIBUTES
+        .iter()
+        .any(|&(kind, attribute)| kind != "*" && attribute == name)
+        || PROPERTIES
+            .iter()
+            .any(|&(kind, property, _)| kind != "*" && property == key)
+}
+
+/// how `key` is applied to an element of `kind`, only props every element
+/// shares are known without a kind
+#[inline]
+pub fn prop_kind(kind: Option<&str>, key: &str) -> PropKind {
+    let name = attribute_name(key);
+    let matches = |entry_kind: &str| entry_kind == "*" || Some(entry_kind) == kind;
+
+    if BOOLEAN_ATTRIBUTES
+        .iter()
+        .any(|&(entry_kind, attribute)| attribute == name && matches(entry_kind))
+    {
+        PropKind::BooleanAttribute
+    } else if PROPERTIES
+        .iter()
+        .any(|&(entry_kind, property, _)| property == key && matches(entry_kind))
+    {
+        PropKind::Property
+    } else {
+        PropKind::Attribute
+    }
+}
+
+/// what a removed `PropKind::Property` is set back to
+#[inline]
+pub fn property_default(kind: Option<&str>, key: &str) -> Value {
+    PROPERTIES
+        .iter()
+        .find(|&&(entry_kind, property, _)| {
+            property == key && (entry_kind == "*" || Some(entry_kind) == kind)
+        })
+        .map_or(Value::Null, |&(_, _, default)| match default {
+            PropertyDefault::False => Value::Bool(false),
+            PropertyDefault::Zero => Value::Number(0.into()),
+            PropertyDefault::One => Value::Number(1.into()),
+        })
+}
+
+/// whether a boolean attribute set to `value` is present, anything but
+/// `false` and `null` is
+#[inline]
+pub fn is_present(value: &Value) -> bool {
+    match value {
+        &Value::Null | &Value::Bool(false) => false,
+        _ => true,
+    }
+}
+
+/// whether `key` set to `value` is a dom property on an element of `kind`,
+/// objects other than `class`, `style` and `attributes` always are
+#[inline]
+pub fn is_property(kind: Option<&str>, key: &str, value: &Value) -> bool {
+    match value {
+        &Value::Null => false,
+        _ if is_class(key) => false,
+        &Value::Object(_) => key != "style" && key != "attributes",
+        _ => prop_kind(kind, key) == PropKind::Property,
+    }
+}
+
+/// the attributes an element of `kind` with `props` has, in html and after
+/// patching, properties and absent boolean attributes are left out
+#[inline]
+pub fn props_attributes(kind: Option<&str>, props: &Map<String, Value>) -> Vec<(String, String)> {
+    let mut attributes = Vec::new();
+
+    for (key, value) in props {
+        push_prop_attributes(kind, key, value, &mut attributes);
+    }
+    attributes
+}
+
+#[inline]
+fn push_prop_attributes(
+    kind: Option<&str>,
+    key: &str,
+    value: &Value,
+    attributes: &mut Vec<(String, String)>,
+) {
+    if key == PORTAL_PROP || key == RAW_HTML_PROP || key == REF_PROP
+        || is_property(kind, key, value)
+    {
+        return;
+    } else if is_class(key) {
+        let class = class_string(value);
+
+        if !class.is_empty() {
+            attributes.push(("class".into(), class));
+        }
+        return;
+    } else if key == "style" {
+        let style = style_string(value);
+
+        if !style.is_empty() {
+            attributes.push(("style".into(), style));
+        }
+        return;
+    }
+
+    match value {
+        &Value::Null => (),
+        &Value::Object(ref map) => if key == "attributes" {
+            for (attr_key, attr_value) in map {
+                if !attr_value.is_null() {
+                    attributes.push((attr_key.clone(), prop_to_html_string(attr_value)));
+                }
+            }
+        } else {
+            attributes.push((key.into(), prop_to_html_string(value)));
+        },
+        value => match prop_kind(kind, key) {
+            PropKind::Attribute => {
+                attributes.push((attribute_name(key).into(), prop_to_html_string(value)));
+            }
+            PropKind::BooleanAttribute => if is_present(value) {
+                attributes.push((attribute_name(key).into(), String::new()));
+            },
+            PropKind::Property => (),
+        },
+    }
+}
+
+#[test]
+fn test_prop_kind() {
+    assert_eq!(prop_kind(Some("button"), "disabled"), PropKind::BooleanAttribute);
+    assert_eq!(prop_kind(Some("a"), "disabled"), PropKind::Attribute);
+    assert_eq!(prop_kind(None, "hidden"), PropKind::BooleanAttribute);
+    assert_eq!(prop_kind(Some("input"), "readOnly"), PropKind::BooleanAttribute);
+    assert_eq!(prop_kind(None, "autoFocus"), PropKind::BooleanAttribute);
+    assert_eq!(prop_kind(Some("form"), "noValidate"), PropKind::BooleanAttribute);
+    assert_eq!(prop_kind(Some("video"), "volume"), PropKind::Property);
+    assert_eq!(attribute_name("className"), "class");
+    assert_eq!(attribute_name("viewBox"), "viewBox");
+    assert!(is_element_prop("disabled"));
+    assert!(!is_element_prop("hidden"));
+    assert_eq!(property_default(Some("input"), "indeterminate"), json!(false));
+
+    let props = json!({
+        "autoFocus": false,
+        "className": "a",
+        "disabled": false,
+        "hidden": true,
+        "multiple": true,
+        "indeterminate": true,
+        "portal": "body",
+    });
+    assert_eq!(
+        props_attributes(Some("input"), props.as_object().unwrap()),
+        vec![
+            ("class".to_string(), "a".to_string()),
+            ("hidden".to_string(), String::new()),
+            ("multiple".to_string(), String::new()),
+        ]
+    );
+
+    let props = json!({ "autoPlay": true, "playsInline": true, "muted": false });
+    assert_eq!(
+        props_attributes(Some("video"), props.as_object().unwrap()),
+        vec![
+            ("autoplay".to_string(), String::new()),
+            ("playsinline".to_string(), String::new()),
+        ]
+    );
+}
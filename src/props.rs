@@ -0,0 +1,287 @@
+use serde_json::{Map, Value};
+
+use super::{Dom, NodesIds, PatchReport};
+use super::class_list::{class_names, class_string, is_class};
+use super::controlled::{controls, remove_controlled, set_controlled};
+use super::namespace::{attribute_local_name, attribute_namespace};
+use super::patcher::{detach_portals, remove_child_nodes_handle};
+use super::portal::PORTAL_PROP;
+use super::prop_kind::{attribute_name, is_element_prop, is_present, prop_kind, property_default,
+                       PropKind};
+use super::raw_html::{raw_html, RAW_HTML_PROP};
+use super::refs::{ref_name, REF_PROP};
+use super::style::style_declarations;
+use super::utils::prop_to_html_string;
+
+#[inline]
+pub fn set_props<D: Dom>(
+    document: &D,
+    nodes_ids: &NodesIds<D>,
+    node: &D::Node,
+    props: &Map<String, Value>,
+    report: &mut PatchReport,
+) {
+    for (key, value) in props {
+        set_prop(document, nodes_ids, node, key, value, report);
+    }
+}
+
+#[inline]
+pub fn set_prop<D: Dom>(
+    document: &D,
+    nodes_ids: &NodesIds<D>,
+    node: &D::Node,
+    key: &str,
+    value: &Value,
+    report: &mut PatchReport,
+) {
+    if key == PORTAL_PROP {
+        return;
+    } else if key == RAW_HTML_PROP {
+        set_raw_html(document, nodes_ids, node, raw_html(value));
+        report.properties_set += 1;
+        return;
+    } else if key == REF_PROP {
+        if let Some(name) = ref_name(value) {
+            nodes_ids.set_ref(name, node);
+        }
+        return;
+    } else if controls(document, node, key) {
+        set_controlled(document, nodes_ids, node, key, value);
+        report.properties_set += 1;
+        return;
+    } else if is_class(key) {
+        let class = class_string(value);
+
+        if !class.is_empty() {
+            set_attribute(document, node, "class", &class);
+            report.attributes_set += 1;
+        }
+        return;
+    } else if key == "style" {
+        update_style(document, node, &Value::Null, value, report);
+        return;
+    }
+
+    match value {
+        &Value::Null => (),
+        &Value::Object(ref map) => if key == "attributes" {
+            for (attr_key, attr_value) in map {
+                set_attribute(document, node, attr_key, &value_to_attribute(attr_value));
+                report.attributes_set += 1;
+            }
+        } else {
+            document.set_property(node, key, value);
+            report.properties_set += 1;
+        },
+        value => {
+            let kind = node_kind(document, node, key);
+
+            match prop_kind(kind.as_ref().map(String::as_str), key) {
+                PropKind::Attribute => {
+                    set_attribute(document, node, attribute_name(key), &value_to_attribute(value));
+                    report.attributes_set += 1;
+                }
+                PropKind::BooleanAttribute => if is_present(value) {
+                    set_attribute(document, node, attribute_name(key), "");
+                    report.attributes_set += 1;
+                } else {
+                    remove_attribute(document, node, attribute_name(key));
+                    report.attributes_removed += 1;
+                },
+                PropKind::Property => {
+                    document.set_property(node, key, value);
+                    report.properties_set += 1;
+                }
+            }
+        }
+    }
+}
+
+/// sets `key` from `prev_prop` to `value`, only touching the class names
+/// and style properties that changed for `class` and `style`, where
+/// `set_prop` sets the whole attribute
+#[inline]
+pub fn update_prop<D: Dom>(
+    document: &D,
+    nodes_ids: &NodesIds<D>,
+    node: &D::Node,
+    key: &str,
+    prev_prop: &Value,
+    value: &Value,
+    report: &mut PatchReport,
+) {
+    if key == REF_PROP {
+        remove_prop(document, nodes_ids, node, key, prev_prop, report);
+        set_prop(document, nodes_ids, node, key, value, report);
+    } else if is_class(key) {
+        update_class(document, node, prev_prop, value, report);
+    } else if key == "style" {
+        update_style(document, node, prev_prop, value, report);
+    } else {
+        set_prop(document, nodes_ids, node, key, value, report);
+    }
+}
+
+#[inline]
+pub fn remove_prop<D: Dom>(
+    document: &D,
+    nodes_ids: &NodesIds<D>,
+    node: &D::Node,
+    key: &str,
+    prev_prop: &Value,
+    report: &mut PatchReport,
+) {
+    if key == PORTAL_PROP {
+        return;
+    } else if key == RAW_HTML_PROP {
+        set_raw_html(document, nodes_ids, node, None);
+        report.properties_removed += 1;
+    } else if key == REF_PROP {
+        if let Some(name) = ref_name(prev_prop) {
+            let ref_node = nodes_ids.ref_node(name);
+
+            // the name may have moved to another node since
+            if ref_node.map_or(false, |ref_node| D::node_key(&ref_node) == D::node_key(node)) {
+                nodes_ids.remove_ref(name);
+            }
+        }
+    } else if controls(document, node, key) {
+        remove_controlled(document, nodes_ids, node, key);
+        report.properties_removed += 1;
+    } else if is_class(key) {
+        update_class(document, node, prev_prop, &Value::Null, report);
+    } else if key == "attributes" {
+        if let &Value::Object(ref map) = prev_prop {
+            for (attr_key, _) in map {
+                remove_attribute(document, node, attr_key);
+                report.attributes_removed += 1;
+            }
+        }
+    } else if key == "style" {
+        update_style(document, node, prev_prop, &Value::Null, report);
+    } else if prev_prop.is_object() {
+        document.set_property(node, key, &Value::Null);
+        report.properties_removed += 1;
+    } else {
+        let kind = node_kind(document, node, key);
+        let kind = kind.as_ref().map(String::as_str);
+
+        if prop_kind(kind, key) == PropKind::Property {
+            document.set_property(node, key, &property_default(kind, key));
+            report.properties_removed += 1;
+        } else {
+            remove_attribute(document, node, attribute_name(key));
+            report.attributes_removed += 1;
+        }
+    }
+}
+
+/// replaces the content of `node` with `html`, or empties it, marking it
+/// opaque to `NodesIds` while it holds raw html, the ids and portals of the
+/// children it replaces are forgotten first
+#[inline]
+fn set_raw_html<D: Dom>(document: &D, nodes_ids: &NodesIds<D>, node: &D::Node, html: Option<&str>) {
+    let child_nodes = document.child_nodes(node);
+
+    for child_node in &child_nodes {
+        let child_node = nodes_ids.portal(child_node).unwrap_or(child_node.clone());
+
+        if let Some(handle) = nodes_ids.handle(&child_node) {
+            remove_child_nodes_handle(document, nodes_ids, handle);
+        }
+    }
+    for child_node in &child_nodes {
+        detach_portals(document, nodes_ids, child_node);
+    }
+    document.set_inner_html(node, html.unwrap_or(""));
+    nodes_ids.set_opaque(node, html.is_some());
+}
+
+/// the kind of `node`, only asked for when `key` depends on it
+#[inline]
+fn node_kind<D: Dom>(document: &D, node: &D::Node, key: &str) -> Option<String> {
+    if is_element_prop(key) {
+        document.node_kind(node)
+    } else {
+        None
+    }
+}
+
+/// removes the class names of `prev_prop` that are not in `value` and adds
+/// the new ones through the `classList`, leaving classes set by others alone
+#[inline]
+pub fn update_class<D: Dom>(
+    document: &D,
+    node: &D::Node,
+    prev_prop: &Value,
+    value: &Value,
+    report: &mut PatchReport,
+) {
+    let prev_names = class_names(prev_prop);
+    let names = class_names(value);
+
+    for name in prev_names.iter().filter(|name| !names.contains(*name)) {
+        document.remove_class(node, name);
+        report.classes_removed += 1;
+    }
+    for name in names.iter().filter(|name| !prev_names.contains(*name)) {
+        document.add_class(node, name);
+        report.classes_added += 1;
+    }
+
+    let class = document.attribute(node, "class");
+    if names.is_empty() && class.map_or(false, |class| class.trim().is_empty()) {
+        document.remove_attribute(node, "class");
+        report.attributes_removed += 1;
+    }
+}
+
+/// removes the style properties of `prev_prop` that are not in `value` and
+/// sets the ones that are new or changed, leaving others' styles alone
+#[inline]
+pub fn update_style<D: Dom>(
+    document: &D,
+    node: &D::Node,
+    prev_prop: &Value,
+    value: &Value,
+    report: &mut PatchReport,
+) {
+    let prev_declarations = style_declarations(prev_prop);
+    let declarations = style_declarations(value);
+
+    for prev in &prev_declarations {
+        if !declarations.iter().any(|declaration| declaration.name == prev.name) {
+            document.remove_style(node, &prev.name);
+            report.styles_removed += 1;
+        }
+    }
+    for declaration in declarations.iter().filter(|d| !prev_declarations.contains(d)) {
+        document.set_style(node, &declaration.name, &declaration.value, declaration.important);
+        report.styles_set += 1;
+    }
+}
+
+/// sets an attribute, using `setAttributeNS` for prefixed names like `xlink:href`
+#[inline]
+pub fn set_attribute<D: Dom>(document: &D, node: &D::Node, key: &str, value: &str) {
+    if let Some(namespace) = attribute_namespace(key) {
+        document.set_attribute_ns(node, namespace, key, value);
+    } else {
+        document.set_attribute(node, key, value);
+    }
+}
+
+#[inline]
+pub fn remove_attribute<D: Dom>(document: &D, node: &D::Node, key: &str) {
+    if let Some(namespace) = attribute_namespace(key) {
+        document.remove_attribute_ns(node, namespace, attribute_local_name(key));
+    } else {
+        document.remove_attribute(node, key);
+    }
+}
+
+#[inline]
+pub fn value_to_attribute(value: &Value) -> String {
+    prop_to_html_string(value)
+}
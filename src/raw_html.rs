@@ -0,0 +1,46 @@
+use serde_json::Value;
+use virtual_view::RawView;
+
+/// the prop that sets an element's content to a string of html, which is
+/// put in through `innerHTML` and rendered by `ToHtmlString` as is, without
+/// escaping, so it must never hold untrusted markup
+///
+/// its value is an object with the html in `__html`, the element's view
+/// children are ignored and the nodes the html creates are never given ids,
+/// patches can not reach into them
+pub const RAW_HTML_PROP: &'static str = "dangerouslySetInnerHTML";
+
+/// the html in a raw html prop's `value`, `{ "__html": "<b>a</b>" }`
+#[inline]
+pub fn raw_html(value: &Value) -> Option<&str> {
+    match value.get("__html") {
+        Some(&Value::String(ref html)) => Some(html),
+        _ => None,
+    }
+}
+
+/// the raw html `view` is given, if any
+#[inline]
+pub fn view_raw_html(view: &RawView) -> Option<&str> {
+    match view {
+        &RawView::Data { ref props, .. } => props.get(RAW_HTML_PROP).and_then(raw_html),
+        &RawView::Text(_) => None,
+    }
+}
+
+/// the children built for `view`, none when it has raw html
+#[inline]
+pub fn view_children(view: &RawView) -> &[RawView] {
+    match view {
+        _ if view_raw_html(view).is_some() => &[],
+        &RawView::Data { ref children, .. } => children,
+        &RawView::Text(_) => &[],
+    }
+}
+
+#[test]
+fn test_raw_html() {
+    assert_eq!(raw_html(&json!({ "__html": "<b>a</b>" })), Some("<b>a</b>"));
+    assert_eq!(raw_html(&json!("<b>a</b>")), None);
+    assert_eq!(raw_html(&json!({ "html": "<b>a</b>" })), None);
+}
@@ -0,0 +1,36 @@
+use std::rc::Rc;
+use std::cell::RefCell;
+
+use virtual_view::{Prop, Transaction};
+
+use super::{Recording, RecordingError};
+
+/// a shared handle a `Patcher` records into, see `Patcher::set_recorder`
+#[derive(Clone, Default)]
+pub struct Recorder(Rc<RefCell<Recording>>);
+
+impl Recorder {
+    #[inline(always)]
+    pub fn new() -> Self {
+        Recorder::default()
+    }
+
+    /// a copy of everything recorded so far
+    #[inline]
+    pub fn recording(&self) -> Recording {
+        self.0.borrow().clone()
+    }
+    #[inline]
+    pub fn clear(&self) {
+        self.0.borrow_mut().clear();
+    }
+
+    #[inline]
+    pub fn record_transaction(&self, transaction: &Transaction) -> Result<(), RecordingError> {
+        self.0.borrow_mut().push_transaction(transaction)
+    }
+    #[inline]
+    pub fn record_event(&self, id: &str, name: &str, event: &Prop) -> Result<(), RecordingError> {
+        self.0.borrow_mut().push_event(id, name, event)
+    }
+}
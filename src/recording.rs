@@ -0,0 +1,277 @@
+use std::error::Error;
+use std::fmt;
+
+use serde_json::{self, Map, Number, Value};
+use virtual_view::{Prop, Transaction};
+
+use super::{Dom, PatchError, Patcher};
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Record {
+    /// a transaction as the patcher received it
+    Transaction(Value),
+    /// an event the patcher dispatched back to its `EventManager`
+    Event { id: String, name: String, event: Value },
+}
+
+impl Record {
+    #[inline]
+    pub fn to_json(&self) -> Value {
+        let mut map = Map::new();
+
+        match self {
+            &Record::Transaction(ref transaction) => {
+                map.insert("transaction".into(), transaction.clone());
+            }
+            &Record::Event {
+                ref id,
+                ref name,
+                ref event,
+            } => {
+                let mut event_map = Map::new();
+                event_map.insert("id".into(), Value::String(id.clone()));
+                event_map.insert("name".into(), Value::String(name.clone()));
+                event_map.insert("event".into(), event.clone());
+                map.insert("event".into(), Value::Object(event_map));
+            }
+        }
+
+        Value::Object(map)
+    }
+
+    #[inline]
+    pub fn from_json(value: &Value) -> Option<Self> {
+        if let Some(transaction) = value.get("transaction") {
+            Some(Record::Transaction(transaction.clone()))
+        } else if let Some(event) = value.get("event") {
+            match (event.get("id"), event.get("name")) {
+                (Some(&Value::String(ref id)), Some(&Value::String(ref name))) => {
+                    Some(Record::Event {
+                        id: id.clone(),
+                        name: name.clone(),
+                        event: event.get("event").cloned().unwrap_or(Value::Null),
+                    })
+                }
+                _ => None,
+            }
+        } else {
+            None
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum RecordingError {
+    /// the record at `index`, or the line it is read from, is not valid
+    Json { index: usize, message: String },
+    /// the transaction at `index` failed to patch
+    Patch { index: usize, error: PatchError },
+}
+
+impl fmt::Display for RecordingError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            &RecordingError::Json { index, ref message } => {
+                write!(f, "invalid record {}: {}", index, message)
+            }
+            &RecordingError::Patch { index, ref error } => {
+                write!(f, "record {} failed to patch: {}", index, error)
+            }
+        }
+    }
+}
+
+impl Error for RecordingError {}
+
+/// the transactions a `Patcher` received and the events it dispatched, in
+/// order, stored as json lines with one record per line
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Recording {
+    records: Vec<Record>,
+}
+
+impl Recording {
+    #[inline(always)]
+    pub fn new() -> Self {
+        Recording::default()
+    }
+
+    #[inline]
+    pub fn records(&self) -> &[Record] {
+        &self.records
+    }
+    #[inline]
+    pub fn push(&mut self, record: Record) {
+        self.records.push(record);
+    }
+    #[inline]
+    pub fn clear(&mut self) {
+        self.records.clear();
+    }
+
+    /// records `transaction`, or nothing when it can not be stored as json
+    #[inline]
+    pub fn push_transaction(&mut self, transaction: &Transaction) -> Result<(), RecordingError> {
+        let value = serde_json::to_value(transaction).map_err(|e| RecordingError::Json {
+            index: self.records.len(),
+            message: e.to_string(),
+        })?;
+
+        self.push(Record::Transaction(value));
+        Ok(())
+    }
+    /// records `event`, or nothing when it can not be stored as json
+    #[inline]
+    pub fn push_event(&mut self, id: &str, name: &str, event: &Prop) -> Result<(), RecordingError> {
+        let value = prop_to_value(event).map_err(|message| RecordingError::Json {
+            index: self.records.len(),
+            message: message,
+        })?;
+
+        self.push(Record::Event {
+            id: id.into(),
+            name: name.into(),
+            event: value,
+        });
+        Ok(())
+    }
+
+    #[inline]
+    pub fn to_json_lines(&self) -> String {
+        let mut out = String::new();
+
+        for record in &self.records {
+            out.push_str(&record.to_json().to_string());
+            out.push('\n');
+        }
+
+        out
+    }
+
+    #[inline]
+    pub fn from_json_lines(json_lines: &str) -> Result<Self, RecordingError> {
+        let mut recording = Recording::new();
+
+        for (index, line) in json_lines.lines().filter(|l| !l.trim().is_empty()).enumerate() {
+            let value: Value = serde_json::from_str(line).map_err(|e| RecordingError::Json {
+                index: index,
+                message: e.to_string(),
+            })?;
+            let record = Record::from_json(&value).ok_or_else(|| RecordingError::Json {
+                index: index,
+                message: "expected a transaction or an event".into(),
+            })?;
+
+            recording.push(record);
+        }
+
+        Ok(recording)
+    }
+
+    /// patches every recorded transaction into `patcher` and dispatches every
+    /// recorded event to its `EventManager`, in the order they were recorded
+    #[inline]
+    pub fn replay<D: Dom>(&self, patcher: &mut Patcher<D>) -> Result<(), RecordingError> {
+        for (index, record) in self.records.iter().enumerate() {
+            match record {
+                &Record::Transaction(ref value) => {
+                    let transaction: Transaction = serde_json::from_value(value.clone())
+                        .map_err(|e| RecordingError::Json {
+                            index: index,
+                            message: e.to_string(),
+                        })?;
+
+                    patcher
+                        .try_patch(&transaction)
+                        .map_err(|error| RecordingError::Patch {
+                            index: index,
+                            error: error,
+                        })?;
+                }
+                &Record::Event {
+                    ref id,
+                    ref name,
+                    ref event,
+                } => {
+                    patcher.dispatch_event(id, name, Prop::from(event));
+                }
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// the json of `prop`, functions and numbers json has no room for, like
+/// `NaN`, can not be converted
+#[inline]
+fn prop_to_value(prop: &Prop) -> Result<Value, String> {
+    match prop {
+        &Prop::Null => Ok(Value::Null),
+        &Prop::Boolean(value) => Ok(Value::Bool(value)),
+        &Prop::Number(value) => match Number::from_f64(value) {
+            Some(number) => Ok(Value::Number(number)),
+            None => Err(format!("{} can not be converted to a json number", value)),
+        },
+        &Prop::String(ref value) => Ok(Value::String(value.clone())),
+        &Prop::Function(_) => Err("a function can not be converted to json".into()),
+        &Prop::Array(ref array) => array
+            .into_iter()
+            .map(prop_to_value)
+            .collect::<Result<Vec<Value>, String>>()
+            .map(Value::Array),
+        &Prop::Object(ref props) => props
+            .into_iter()
+            .map(|(key, value)| prop_to_value(value).map(|value| (key.clone(), value)))
+            .collect::<Result<Map<String, Value>, String>>()
+            .map(Value::Object),
+    }
+}
+
+#[test]
+fn test_recording() {
+    use virtual_view::{Patch, Props};
+
+    use super::{Batch, Recorder};
+    use super::tests::memory_patcher;
+
+    let (document, root, mut patcher) = memory_patcher();
+    let recorder = Recorder::new();
+    let id = "0".to_string();
+
+    let mut batch = Batch::new();
+    batch.push_patch(&id, &Patch::Mount((&view! { <button/> }).into()));
+    batch.push_event(&id, &"onclick".to_string(), true);
+    patcher.try_patch_batch(&batch).unwrap();
+    patcher.set_recorder(Some(recorder.clone()));
+
+    let node = patcher.node(&id).unwrap();
+    document.dispatch_event(&node, "click", Prop::Null);
+
+    let recording = recorder.recording();
+    assert_eq!(
+        recording.records(),
+        &[
+            Record::Event {
+                id: id.clone(),
+                name: "click".into(),
+                event: Value::Null,
+            },
+        ]
+    );
+    assert_eq!(
+        Recording::from_json_lines(&recording.to_json_lines()).unwrap(),
+        recording
+    );
+    assert!(Recording::from_json_lines("{\"other\": 1}").is_err());
+
+    recorder.clear();
+    recording.replay(&mut patcher).unwrap();
+    assert_eq!(recorder.recording(), recording);
+
+    let mut recording = Recording::new();
+    assert!(recording.push_event(&id, "click", &Prop::Number(::std::f64::NAN)).is_err());
+    let function = Prop::from(|_: &mut Props| Prop::Null);
+    assert!(recording.push_event(&id, "click", &function).is_err());
+    assert!(recording.records().is_empty());
+}
@@ -0,0 +1,71 @@
+use std::rc::Rc;
+use std::cell::RefCell;
+
+use messenger::Messenger;
+use serde_json::{Map, Value};
+
+use super::{Dom, Patcher};
+
+/// the prop that registers an element with its `Patcher` under the prop's
+/// string value, see `Patcher::ref_node`, a name given to another element
+/// moves to it
+pub const REF_PROP: &'static str = "ref";
+
+/// the messenger request resolving a ref name to its view id, `null` when
+/// no mounted element has the name
+pub const REF_REQUEST: &'static str = "virtual_view_dom.ref";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RefChangeKind {
+    /// an element got the name
+    Mounted,
+    /// the name moved to another element, like the one a `Patch::Replace`
+    /// created
+    Replaced,
+    /// the element with the name was removed or lost its ref prop
+    Removed,
+}
+
+/// a change to a ref, passed to the `Patcher`'s ref listener once the patch,
+/// or batch, causing it is applied
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RefChange {
+    pub name: String,
+    /// the view id of the element the name refers to, or referred to when
+    /// removed
+    pub id: String,
+    pub kind: RefChangeKind,
+}
+
+/// the ref name in a ref prop's `value`
+#[inline]
+pub fn ref_name(value: &Value) -> Option<&str> {
+    match value {
+        &Value::String(ref name) if !name.is_empty() => Some(name),
+        _ => None,
+    }
+}
+
+/// answers `REF_REQUEST`s from `messenger`, `{ "name": "input" }` or just
+/// `"input"`, with `{ "id": "..." }`
+#[inline]
+pub fn listen_ref_requests<D: Dom>(
+    messenger: &Messenger<Value>,
+    patcher: &Rc<RefCell<Patcher<D>>>,
+) {
+    let patcher = patcher.clone();
+
+    let _ = messenger.on(REF_REQUEST, move |data: &Value| {
+        let name = data.get("name").unwrap_or(data);
+        let id = ref_name(name).and_then(|name| patcher.borrow().ref_id(name));
+
+        Some(match id {
+            Some(id) => {
+                let mut response = Map::new();
+                response.insert("id".into(), Value::String(id));
+                Value::Object(response)
+            }
+            None => Value::Null,
+        })
+    });
+}
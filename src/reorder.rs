@@ -0,0 +1,186 @@
+use std::collections::VecDeque;
+use std::hash::Hash;
+
+use fnv::FnvHashMap;
+
+use super::{Dom, PatchError};
+
+/// the children of view `id` after a `Patch::Order`, `removes` index the
+/// children left by the removes before them and `inserts` index the children
+/// built so far, like a list spliced in order
+///
+/// unkeyed children removed are put back by the unkeyed inserts in the same
+/// order, children removed and never put back, or inserts of unknown keys,
+/// are left out and keep their place in the dom
+#[inline]
+pub(crate) fn order_nodes<'a, N, K, R, I>(
+    id: &str,
+    child_nodes: Vec<N>,
+    removes: R,
+    inserts: I,
+) -> Result<Vec<N>, PatchError>
+where
+    K: 'a + Hash + Eq,
+    R: IntoIterator<Item = &'a (usize, Option<K>)>,
+    I: IntoIterator<Item = &'a (Option<K>, usize)>,
+{
+    let mut nodes = child_nodes;
+    let mut keyed = FnvHashMap::default();
+    let mut unkeyed = VecDeque::new();
+
+    for &(index, ref key) in removes {
+        if index >= nodes.len() {
+            return Err(PatchError::OrderIndexOutOfRange {
+                id: id.into(),
+                index: index,
+                len: nodes.len(),
+            });
+        }
+        let node = nodes.remove(index);
+
+        match key {
+            &Some(ref key) => {
+                keyed.insert(key, node);
+            }
+            &None => unkeyed.push_back(node),
+        }
+    }
+
+    for &(ref key, index) in inserts {
+        let node = match key {
+            &Some(ref key) => keyed.remove(key),
+            &None => unkeyed.pop_front(),
+        };
+
+        if let Some(node) = node {
+            let index = if index < nodes.len() { index } else { nodes.len() };
+            nodes.insert(index, node);
+        }
+    }
+
+    Ok(nodes)
+}
+
+/// moves `parent`'s children into the order of `nodes` with as few moves as
+/// possible, returns how many were moved
+///
+/// the longest run of `nodes` already in order stays where it is and every
+/// other node is inserted before its next sibling, nothing is removed first,
+/// so nodes that do not move keep focus, scroll position and playback
+#[inline]
+pub(crate) fn reorder_children<D: Dom>(
+    document: &D,
+    parent: &D::Node,
+    nodes: &[D::Node],
+) -> usize {
+    let positions: FnvHashMap<D::NodeKey, usize> = document
+        .child_nodes(parent)
+        .iter()
+        .enumerate()
+        .map(|(position, child)| (D::node_key(child), position))
+        .collect();
+
+    let mut indices = Vec::new();
+    let mut current = Vec::new();
+    for (index, node) in nodes.iter().enumerate() {
+        if let Some(position) = positions.get(&D::node_key(node)) {
+            indices.push(index);
+            current.push(*position);
+        }
+    }
+
+    let mut stays = vec![false; nodes.len()];
+    for i in longest_increasing_subsequence(&current) {
+        stays[indices[i]] = true;
+    }
+
+    let mut moved = 0;
+    for index in (0..nodes.len()).rev() {
+        if !stays[index] {
+            match nodes.get(index + 1) {
+                Some(next) => document.insert_before(parent, &nodes[index], next),
+                None => document.append_child(parent, &nodes[index]),
+            }
+            moved += 1;
+        }
+    }
+    moved
+}
+
+/// the indices of a longest strictly increasing subsequence of `values`
+#[inline]
+pub(crate) fn longest_increasing_subsequence(values: &[usize]) -> Vec<usize> {
+    let mut previous = vec![0; values.len()];
+    // tails[i] is the index of the smallest value ending a subsequence of i + 1
+    let mut tails: Vec<usize> = Vec::new();
+
+    for (index, value) in values.iter().enumerate() {
+        let position = match tails.binary_search_by(|&tail| values[tail].cmp(value)) {
+            Ok(position) | Err(position) => position,
+        };
+
+        if position > 0 {
+            previous[index] = tails[position - 1];
+        }
+        if position == tails.len() {
+            tails.push(index);
+        } else {
+            tails[position] = index;
+        }
+    }
+
+    let mut subsequence = vec![0; tails.len()];
+    if let Some(&last) = tails.last() {
+        let mut index = last;
+
+        for i in (0..subsequence.len()).rev() {
+            subsequence[i] = index;
+            index = previous[index];
+        }
+    }
+    subsequence
+}
+
+#[test]
+fn test_longest_increasing_subsequence() {
+    assert_eq!(longest_increasing_subsequence(&[]), Vec::<usize>::new());
+    assert_eq!(longest_increasing_subsequence(&[0, 1, 2]), vec![0, 1, 2]);
+    assert_eq!(longest_increasing_subsequence(&[2, 1, 0]), vec![2]);
+    assert_eq!(longest_increasing_subsequence(&[3, 1, 2, 0, 4]), vec![1, 2, 4]);
+}
+
+#[test]
+fn test_reorder_children() {
+    use super::MemoryDocument;
+
+    let document = MemoryDocument::new();
+    let parent = document.create_element("ul");
+    let children: Vec<_> = ["a", "b", "c", "d", "e"]
+        .iter()
+        .map(|text| {
+            let child = document.create_text_node(text);
+            document.append_child(&parent, &child);
+            child
+        })
+        .collect();
+
+    let key = |key: &str| Some(key.to_string());
+    // move "a" to the end, remove "c" and put it back first, "e" is unkeyed
+    let removes = vec![(0, key("a")), (1, key("c")), (2, None)];
+    let inserts = vec![(key("c"), 0), (None, 3), (key("a"), 4), (key("x"), 0)];
+    let nodes = order_nodes("0", children.clone(), &removes, &inserts).unwrap();
+
+    assert_eq!(reorder_children(&document, &parent, &nodes), 2);
+    assert_eq!(parent.inner_html(), "cbdea");
+    assert!(children.iter().all(|child| child.parent().is_some()));
+
+    assert_eq!(reorder_children(&document, &parent, &nodes), 0);
+    assert_eq!(
+        order_nodes("0", children, &[(5, key("a"))], &inserts),
+        Err(PatchError::OrderIndexOutOfRange {
+            id: "0".into(),
+            index: 5,
+            len: 5,
+        })
+    );
+}
@@ -0,0 +1,322 @@
+use serde_json::{Map, Value};
+use virtual_view::{view_id, Patch, RawView};
+
+use super::{Dom, PatchErrorPolicy, PatchReport, Patcher};
+use super::props::{remove_prop, update_prop};
+use super::raw_html::RAW_HTML_PROP;
+use super::refs::{ref_name, REF_PROP};
+use super::reorder::reorder_children;
+
+enum Undo<N> {
+    /// detaches and forgets the subtree a mount or insert created
+    Detach { id: String },
+    /// puts back the subtree a replace swapped out
+    Restore {
+        id: String,
+        node: N,
+        anchor: N,
+        ids: Vec<(String, N, Option<String>)>,
+        portals: Vec<(N, N, N)>,
+    },
+    /// puts the children back in their order before a `Patch::Order`
+    Reorder { node: N, children: Vec<N> },
+    Props {
+        node: N,
+        prev_props: Map<String, Value>,
+        diff_props: Map<String, Value>,
+    },
+    /// reinserts a removed subtree where it was
+    Reinsert {
+        parent: N,
+        next: Option<N>,
+        anchor: N,
+        ids: Vec<(String, N, Option<String>)>,
+        portals: Vec<(N, N, N)>,
+    },
+}
+
+/// the inverse of every patch and remove applied so far, recorded before
+/// each is applied when the patcher's `PatchErrorPolicy` is `Rollback`, so
+/// a failure midway can put the dom and the node ids back as they were
+pub(crate) struct Rollback<D: Dom> {
+    undos: Vec<Undo<D::Node>>,
+}
+
+impl<D: Dom> Rollback<D> {
+    #[inline(always)]
+    pub fn new() -> Self {
+        Rollback { undos: Vec::new() }
+    }
+
+    #[inline]
+    pub fn record_patch(&mut self, patcher: &Patcher<D>, id: &String, patch: &Patch) {
+        if patcher.error_policy() != PatchErrorPolicy::Rollback {
+            return;
+        }
+        let document = patcher.document();
+        let node = patcher.node(id);
+
+        if let (&Patch::Props(_, ref diff_props), &Some(ref node)) = (patch, &node) {
+            self.record_raw_html(patcher, node, diff_props);
+        }
+
+        let undo = match (patch, node) {
+            (&Patch::Mount(_), _) => Undo::Detach { id: id.clone() },
+            (&Patch::Insert(ref child_id, ..), _) => Undo::Detach {
+                id: child_id.clone(),
+            },
+            (&Patch::Replace(ref prev_view, _), Some(node)) => {
+                let anchor = patcher.nodes_ids().anchor(&node);
+
+                Undo::Restore {
+                    id: id.clone(),
+                    ids: subtree_ids(patcher, id, prev_view),
+                    portals: patcher.attached_portals(&anchor),
+                    node: node,
+                    anchor: anchor,
+                }
+            }
+            (&Patch::Order(_), Some(node)) => Undo::Reorder {
+                children: document.child_nodes(&node),
+                node: node,
+            },
+            (&Patch::Props(ref prev_props, ref diff_props), Some(node)) => Undo::Props {
+                node: node,
+                prev_props: prev_props.clone(),
+                diff_props: diff_props.clone(),
+            },
+            // the patch fails before touching anything without a node
+            _ => return,
+        };
+
+        self.undos.push(undo);
+    }
+
+    /// the view children a newly set raw html replaces, whose ids and portals
+    /// are forgotten with them, are reinserted once the props are undone
+    #[inline]
+    fn record_raw_html(
+        &mut self,
+        patcher: &Patcher<D>,
+        node: &D::Node,
+        diff_props: &Map<String, Value>,
+    ) {
+        let sets_raw_html = diff_props
+            .get(RAW_HTML_PROP)
+            .map_or(false, |value| !value.is_null());
+
+        if !sets_raw_html || patcher.nodes_ids().is_opaque(node) {
+            return;
+        }
+        let child_nodes = patcher.document().child_nodes(node);
+
+        for (index, child_node) in child_nodes.iter().enumerate() {
+            self.undos.push(Undo::Reinsert {
+                ids: dom_subtree_ids(patcher, child_node),
+                portals: patcher.attached_portals(child_node),
+                parent: node.clone(),
+                next: child_nodes.get(index + 1).cloned(),
+                anchor: child_node.clone(),
+            });
+        }
+    }
+
+    #[inline]
+    pub fn record_remove(&mut self, patcher: &Patcher<D>, id: &String, view: &RawView) {
+        if patcher.error_policy() != PatchErrorPolicy::Rollback {
+            return;
+        }
+        let document = patcher.document();
+
+        if let Some(node) = patcher.node(id) {
+            let anchor = patcher.nodes_ids().anchor(&node);
+
+            if let Some(parent) = document.parent_node(&anchor) {
+                let key = D::node_key(&anchor);
+                let next = document
+                    .child_nodes(&parent)
+                    .into_iter()
+                    .skip_while(|child| D::node_key(child) != key)
+                    .nth(1);
+
+                self.undos.push(Undo::Reinsert {
+                    ids: subtree_ids(patcher, id, view),
+                    portals: patcher.attached_portals(&anchor),
+                    parent: parent,
+                    next: next,
+                    anchor: anchor,
+                });
+            }
+        }
+    }
+
+    /// reverts everything recorded, last applied first
+    #[inline]
+    pub fn undo(&mut self, patcher: &mut Patcher<D>) {
+        let mut report = PatchReport::new();
+
+        while let Some(undo) = self.undos.pop() {
+            match undo {
+                Undo::Detach { id } => {
+                    if let Some(node) = patcher.node(&id) {
+                        let anchor = patcher.nodes_ids().anchor(&node);
+
+                        if let Some(parent) = patcher.document().parent_node(&anchor) {
+                            patcher.document().remove_child(&parent, &anchor);
+                        }
+                        patcher.remove_child_nodes_id(&id);
+                        patcher.detach_portals(&anchor);
+                    }
+                }
+                Undo::Restore {
+                    id,
+                    node,
+                    anchor,
+                    ids,
+                    portals,
+                } => {
+                    if let Some(new_node) = patcher.node(&id) {
+                        if D::node_key(&new_node) != D::node_key(&node) {
+                            let new_anchor = patcher.nodes_ids().anchor(&new_node);
+
+                            if let Some(parent) = patcher.document().parent_node(&new_anchor) {
+                                patcher
+                                    .document()
+                                    .replace_child(&parent, &anchor, &new_anchor);
+                            }
+                            patcher.remove_child_nodes_id(&id);
+                            patcher.detach_portals(&new_anchor);
+                        }
+                    }
+                    patcher.reattach_portals(portals);
+                    insert_ids(patcher, ids);
+                }
+                Undo::Reorder { node, children } => {
+                    reorder_children(patcher.document(), &node, &children);
+                }
+                Undo::Props {
+                    node,
+                    prev_props,
+                    diff_props,
+                } => {
+                    let document = patcher.document();
+                    let nodes_ids = patcher.nodes_ids();
+
+                    for (key, value) in diff_props {
+                        match prev_props.get(&key) {
+                            Some(prev) if !prev.is_null() => {
+                                let report = &mut report;
+                                update_prop(document, nodes_ids, &node, &key, &value, prev, report);
+                            }
+                            _ => if !value.is_null() {
+                                remove_prop(document, nodes_ids, &node, &key, &value, &mut report);
+                            },
+                        }
+                    }
+                }
+                Undo::Reinsert {
+                    parent,
+                    next,
+                    anchor,
+                    ids,
+                    portals,
+                } => {
+                    {
+                        let document = patcher.document();
+
+                        if document.parent_node(&anchor).is_none() {
+                            match next {
+                                Some(ref next) if document.parent_node(next).is_some() => {
+                                    document.insert_before(&parent, &anchor, next)
+                                }
+                                _ => document.append_child(&parent, &anchor),
+                            }
+                        }
+                    }
+                    patcher.reattach_portals(portals);
+                    insert_ids(patcher, ids);
+                }
+            }
+        }
+
+        // the refs are as they were, nothing the undone patches queued happened
+        patcher.nodes_ids().take_ref_changes();
+        patcher.document().take_exception();
+    }
+}
+
+/// the id, node and ref name of every node in the subtree of `id`
+#[inline]
+fn subtree_ids<D: Dom>(
+    patcher: &Patcher<D>,
+    id: &String,
+    view: &RawView,
+) -> Vec<(String, D::Node, Option<String>)> {
+    let mut ids = Vec::new();
+    push_subtree_ids(patcher, id, view, &mut ids);
+    ids
+}
+
+#[inline]
+fn push_subtree_ids<D: Dom>(
+    patcher: &Patcher<D>,
+    id: &String,
+    view: &RawView,
+    ids: &mut Vec<(String, D::Node, Option<String>)>,
+) {
+    if let Some(node) = patcher.node(id) {
+        let name = match view {
+            &RawView::Data { ref props, .. } => props.get(REF_PROP).and_then(ref_name),
+            &RawView::Text(_) => None,
+        };
+        ids.push((id.clone(), node, name.map(Into::into)));
+
+        if let &RawView::Data { ref children, .. } = view {
+            for (index, child) in children.iter().enumerate() {
+                let child_id = view_id(id, child.key(), index);
+                push_subtree_ids(patcher, &child_id, child, ids);
+            }
+        }
+    }
+}
+
+/// the id, node and ref name of every node under `node` that has an id,
+/// found through the dom for when there is no view to follow
+#[inline]
+fn dom_subtree_ids<D: Dom>(
+    patcher: &Patcher<D>,
+    node: &D::Node,
+) -> Vec<(String, D::Node, Option<String>)> {
+    let mut ids = Vec::new();
+    push_dom_subtree_ids(patcher, node, &mut ids);
+    ids
+}
+
+#[inline]
+fn push_dom_subtree_ids<D: Dom>(
+    patcher: &Patcher<D>,
+    node: &D::Node,
+    ids: &mut Vec<(String, D::Node, Option<String>)>,
+) {
+    let node = patcher.nodes_ids().portal(node).unwrap_or(node.clone());
+
+    if let Some(id) = patcher.nodes_ids().id(&node) {
+        ids.push((id, node.clone(), patcher.nodes_ids().node_ref(&node)));
+    }
+    for child_node in patcher.document().child_nodes(&node) {
+        push_dom_subtree_ids(patcher, &child_node, ids);
+    }
+}
+
+/// gives the nodes of a subtree back their ids and refs, which forgetting
+/// the ids dropped
+#[inline]
+fn insert_ids<D: Dom>(patcher: &Patcher<D>, ids: Vec<(String, D::Node, Option<String>)>) {
+    for (id, node, name) in ids {
+        patcher.nodes_ids().insert(&id, node.clone());
+
+        if let Some(name) = name {
+            patcher.nodes_ids().set_ref(&name, &node);
+        }
+    }
+}
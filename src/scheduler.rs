@@ -0,0 +1,305 @@
+use std::f64;
+use std::mem;
+use std::ops::Range;
+use std::rc::Rc;
+use std::cell::RefCell;
+
+use futures::task::Task;
+use stdweb::web::Document;
+use virtual_view::Transaction;
+
+use super::{Batch, Dom, PatchError, PatchFuture, PatchTask, Patcher};
+
+struct SchedulerInner<D: Dom> {
+    patcher: Rc<RefCell<Patcher<D>>>,
+    batch: Batch,
+    task: Option<(PatchTask<D>, usize)>,
+    frame_budget: Option<f64>,
+    scheduled: bool,
+    scheduled_count: usize,
+    applied_count: usize,
+    failed: Option<(Range<usize>, PatchError)>,
+    waiting: Vec<Task>,
+}
+
+/// queues transactions and patches them together in the next animation frame,
+/// so any number of updates between two repaints touch the dom only once
+///
+/// with a frame budget a large batch is patched over as many frames as it
+/// needs, spending about that many milliseconds in each of them
+pub struct Scheduler<D: Dom = Document>(Rc<RefCell<SchedulerInner<D>>>);
+
+impl<D: Dom> Clone for Scheduler<D> {
+    #[inline]
+    fn clone(&self) -> Self {
+        Scheduler(self.0.clone())
+    }
+}
+
+impl<D: Dom> Scheduler<D> {
+    #[inline(always)]
+    pub fn new(patcher: Rc<RefCell<Patcher<D>>>) -> Self {
+        Scheduler(Rc::new(RefCell::new(SchedulerInner {
+            patcher: patcher,
+            batch: Batch::new(),
+            task: None,
+            frame_budget: None,
+            scheduled: false,
+            scheduled_count: 0,
+            applied_count: 0,
+            failed: None,
+            waiting: Vec::new(),
+        })))
+    }
+
+    #[inline]
+    pub fn patcher(&self) -> Rc<RefCell<Patcher<D>>> {
+        self.0.borrow().patcher.clone()
+    }
+
+    #[inline]
+    pub fn frame_budget(&self) -> Option<f64> {
+        self.0.borrow().frame_budget
+    }
+    /// milliseconds to patch for in each frame, `None` patches everything
+    /// queued in a single frame
+    #[inline]
+    pub fn set_frame_budget(&self, frame_budget: Option<f64>) {
+        self.0.borrow_mut().frame_budget = frame_budget;
+    }
+
+    #[inline]
+    pub fn is_scheduled(&self) -> bool {
+        self.0.borrow().scheduled
+    }
+
+    /// adds `transaction` to the pending batch and requests an animation
+    /// frame to flush it, if one is not requested already
+    #[inline]
+    pub fn schedule(&self, transaction: &Transaction) {
+        {
+            let mut inner = self.0.borrow_mut();
+            inner.patcher.borrow().record_transaction(transaction);
+            inner.batch.push(transaction);
+            inner.scheduled_count += 1;
+        }
+        self.request_flush();
+    }
+
+    /// resolves once every transaction scheduled so far has been patched,
+    /// or fails with the error that stopped the last of them from patching
+    #[inline]
+    pub fn when_applied(&self) -> PatchFuture<D> {
+        PatchFuture::new(self.clone(), self.0.borrow().scheduled_count)
+    }
+
+    /// true once the first `count` scheduled transactions have been patched
+    #[inline]
+    pub fn is_applied(&self, count: usize) -> bool {
+        self.0.borrow().applied_count >= count
+    }
+
+    /// the error of the last batch that failed, if the transaction `count`
+    /// was in it, the patcher's `PatchErrorPolicy` has already been applied
+    #[inline]
+    pub fn error(&self, count: usize) -> Option<PatchError> {
+        match self.0.borrow().failed {
+            Some((ref range, ref error)) if range.start < count && count <= range.end => {
+                Some(error.clone())
+            }
+            _ => None,
+        }
+    }
+
+    /// patches everything pending right away, ignoring the frame budget
+    #[inline]
+    pub fn flush(&self) {
+        self.0.borrow_mut().scheduled = false;
+        self.run(f64::INFINITY);
+    }
+
+    /// drops everything pending without patching it, futures waiting on it
+    /// resolve as if it was patched
+    #[inline]
+    pub fn cancel(&self) {
+        let waiting = {
+            let mut inner = self.0.borrow_mut();
+            inner.batch = Batch::new();
+            inner.task = None;
+            inner.scheduled = false;
+            inner.applied_count = inner.scheduled_count;
+            mem::replace(&mut inner.waiting, Vec::new())
+        };
+
+        for task in waiting {
+            task.notify();
+        }
+    }
+
+    #[inline]
+    pub(crate) fn wait(&self, task: Task) {
+        self.0.borrow_mut().waiting.push(task);
+    }
+
+    #[inline]
+    fn run_frame(&self) {
+        let frame_budget = {
+            let mut inner = self.0.borrow_mut();
+            inner.scheduled = false;
+            inner.frame_budget.unwrap_or(f64::INFINITY)
+        };
+
+        if !self.run(frame_budget) {
+            self.request_flush();
+        }
+    }
+
+    /// patches for at most `budget` milliseconds, returns true when nothing
+    /// is left to patch
+    #[inline]
+    fn run(&self, budget: f64) -> bool {
+        let patcher = self.patcher();
+        let start = patcher.borrow().document().now();
+
+        loop {
+            let task = {
+                let mut inner = self.0.borrow_mut();
+
+                match inner.task.take() {
+                    Some(task) => Some(task),
+                    None => if inner.batch.is_empty() {
+                        None
+                    } else {
+                        let batch = mem::replace(&mut inner.batch, Batch::new());
+                        Some((PatchTask::new(batch), inner.scheduled_count))
+                    },
+                }
+            };
+            let (mut task, count) = match task {
+                Some(task) => task,
+                None => break,
+            };
+
+            let elapsed = patcher.borrow().document().now() - start;
+            let done = match task.run(&mut patcher.borrow_mut(), budget - elapsed) {
+                Ok(done) => done,
+                Err(error) => {
+                    let mut inner = self.0.borrow_mut();
+                    let applied_count = inner.applied_count;
+                    inner.failed = Some((applied_count..count, error));
+                    true
+                }
+            };
+
+            if done {
+                self.0.borrow_mut().applied_count = count;
+            } else {
+                self.0.borrow_mut().task = Some((task, count));
+                break;
+            }
+            if patcher.borrow().document().now() - start >= budget {
+                break;
+            }
+        }
+
+        let (done, waiting) = {
+            let mut inner = self.0.borrow_mut();
+            let done = inner.task.is_none() && inner.batch.is_empty();
+
+            if done {
+                inner.applied_count = inner.scheduled_count;
+            }
+            (done, mem::replace(&mut inner.waiting, Vec::new()))
+        };
+
+        for task in waiting {
+            task.notify();
+        }
+
+        done
+    }
+
+    #[inline]
+    fn request_flush(&self) {
+        let patcher = {
+            let mut inner = self.0.borrow_mut();
+
+            if inner.scheduled {
+                return;
+            }
+            inner.scheduled = true;
+            inner.patcher.clone()
+        };
+        let scheduler = self.clone();
+
+        patcher
+            .borrow()
+            .document()
+            .request_animation_frame(Box::new(move || if scheduler.is_scheduled() {
+                scheduler.run_frame();
+            }));
+    }
+}
+
+#[test]
+fn test_scheduler() {
+    use futures::Future;
+    use virtual_view::RawView;
+
+    use super::tests::{memory_patcher, props};
+
+    let (document, root, patcher) = memory_patcher();
+    let scheduler = Scheduler::new(Rc::new(RefCell::new(patcher)));
+    let child_id = "0.1".to_string();
+
+    let list: RawView = (&view! { <ul/> }).into();
+    let item: RawView = (&view! { <li>{"a"}</li> }).into();
+
+    let mut transaction = Transaction::new();
+    transaction.mount("0", list);
+    scheduler.schedule(&transaction);
+    scheduler.schedule(&Transaction::new());
+    assert_eq!(document.pending_frames(), 1);
+    assert_eq!(root.inner_html(), "");
+
+    assert_eq!(document.run_animation_frames(), 1);
+    assert_eq!(root.inner_html(), "<ul></ul>");
+    assert!(!scheduler.is_scheduled());
+
+    let mut transaction = Transaction::new();
+    transaction.props("0", props(json!({})), props(json!({ "class": "a", "title": "a" })));
+    scheduler.schedule(&transaction);
+
+    let mut transaction = Transaction::new();
+    transaction.props(
+        "0",
+        props(json!({ "class": "a", "title": "a" })),
+        props(json!({ "class": "b", "title": null })),
+    );
+    transaction.insert("0", &child_id, 0, item.clone());
+    scheduler.schedule(&transaction);
+
+    let mut transaction = Transaction::new();
+    transaction.remove(&child_id, item);
+    scheduler.schedule(&transaction);
+    assert_eq!(document.pending_frames(), 1);
+
+    document.run_animation_frames();
+
+    assert!(scheduler.is_applied(5));
+    assert_eq!(root.inner_html(), "<ul class=\"b\"></ul>");
+    assert!(scheduler.patcher().borrow().node(&child_id).is_none());
+
+    let mut transaction = Transaction::new();
+    transaction.props("missing", props(json!({})), props(json!({ "class": "c" })));
+    scheduler.schedule(&transaction);
+    let applied = scheduler.when_applied();
+
+    document.run_animation_frames();
+
+    assert!(scheduler.is_applied(6));
+    assert_eq!(
+        applied.wait().unwrap_err(),
+        PatchError::UnknownId("missing".to_string())
+    );
+}
@@ -0,0 +1,180 @@
+use serde_json::Value;
+
+use super::utils::prop_to_html_string;
+
+/// properties whose numbers have no unit, others get `px`
+const UNITLESS: &'static [&'static str] = &[
+    "animation-iteration-count",
+    "aspect-ratio",
+    "border-image-outset",
+    "border-image-slice",
+    "border-image-width",
+    "column-count",
+    "columns",
+    "fill-opacity",
+    "flex",
+    "flex-grow",
+    "flex-shrink",
+    "flood-opacity",
+    "font-weight",
+    "grid-area",
+    "grid-column",
+    "grid-column-end",
+    "grid-column-start",
+    "grid-row",
+    "grid-row-end",
+    "grid-row-start",
+    "line-clamp",
+    "line-height",
+    "opacity",
+    "order",
+    "orphans",
+    "stop-opacity",
+    "stroke-dasharray",
+    "stroke-dashoffset",
+    "stroke-miterlimit",
+    "stroke-opacity",
+    "stroke-width",
+    "tab-size",
+    "widows",
+    "z-index",
+    "zoom",
+];
+
+/// a style declaration, a property name in kebab case, its value and
+/// whether it is `!important`
+#[derive(Debug, Clone, PartialEq)]
+pub struct StyleDeclaration {
+    pub name: String,
+    pub value: String,
+    pub important: bool,
+}
+
+/// the declarations of a `style` prop, a map of properties to values or a
+/// css string, names in camel or kebab case, numbers in `px` unless the
+/// property is unitless, `null` and empty values left out
+#[inline]
+pub fn style_declarations(style: &Value) -> Vec<StyleDeclaration> {
+    let mut declarations = Vec::new();
+
+    match style {
+        &Value::Object(ref map) => for (key, value) in map {
+            push_declaration(key, value, &mut declarations);
+        },
+        &Value::String(ref css) => for declaration in css.split(';') {
+            let mut parts = declaration.splitn(2, ':');
+
+            if let (Some(key), Some(value)) = (parts.next(), parts.next()) {
+                push_declaration(key.trim(), &Value::String(value.into()), &mut declarations);
+            }
+        },
+        _ => (),
+    }
+
+    declarations
+}
+
+/// the `style` attribute `style` renders to
+#[inline]
+pub fn style_string(style: &Value) -> String {
+    let mut out = String::new();
+
+    for declaration in style_declarations(style) {
+        out.push_str(&declaration.name);
+        out.push(':');
+        out.push_str(&declaration.value);
+        if declaration.important {
+            out.push_str(" !important");
+        }
+        out.push(';');
+    }
+
+    out
+}
+
+/// `key` in kebab case, `fontSize` is `font-size` and `WebkitTransition` and
+/// `msTransform` are `-webkit-transition` and `-ms-transform`, custom
+/// properties like `--main-color` are kept as they are
+#[inline]
+pub fn style_name(key: &str) -> String {
+    if key.starts_with("--") {
+        return key.into();
+    }
+    let mut name = String::with_capacity(key.len() + 4);
+
+    if key.starts_with("ms") && key[2..].starts_with(char::is_uppercase) {
+        name.push('-');
+    }
+    for c in key.chars() {
+        if c.is_uppercase() {
+            name.push('-');
+            name.extend(c.to_lowercase());
+        } else {
+            name.push(c);
+        }
+    }
+
+    name
+}
+
+#[inline]
+fn push_declaration(key: &str, value: &Value, declarations: &mut Vec<StyleDeclaration>) {
+    let name = style_name(key);
+
+    let value = match value {
+        &Value::Null | &Value::Bool(false) => return,
+        &Value::Number(ref number) => {
+            let is_zero = number.as_f64() == Some(0.0);
+
+            if is_zero || name.starts_with("--") || UNITLESS.contains(&name.as_str()) {
+                number.to_string()
+            } else {
+                format!("{}px", number)
+            }
+        }
+        value => prop_to_html_string(value).trim().to_string(),
+    };
+
+    let (value, important) = if value.ends_with("!important") {
+        let len = value.len() - "!important".len();
+        (value[..len].trim().to_string(), true)
+    } else {
+        (value, false)
+    };
+
+    if value.is_empty() {
+        return;
+    }
+    declarations.retain(|declaration| declaration.name != name);
+    declarations.push(StyleDeclaration {
+        name: name,
+        value: value,
+        important: important,
+    });
+}
+
+#[test]
+fn test_style_declarations() {
+    assert_eq!(style_name("fontSize"), "font-size");
+    assert_eq!(style_name("font-size"), "font-size");
+    assert_eq!(style_name("WebkitTransition"), "-webkit-transition");
+    assert_eq!(style_name("msTransform"), "-ms-transform");
+    assert_eq!(style_name("--mainColor"), "--mainColor");
+
+    let style = json!({
+        "width": 10,
+        "zIndex": 2,
+        "margin": 0,
+        "--gap": 4,
+        "color": "red !important",
+        "display": null,
+    });
+    assert_eq!(
+        style_string(&style),
+        "--gap:4;color:red !important;margin:0;width:10px;z-index:2;"
+    );
+    assert_eq!(
+        style_declarations(&json!("color: red; font-size:2em")),
+        style_declarations(&json!({ "color": "red", "fontSize": "2em" }))
+    );
+}
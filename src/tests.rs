@@ -0,0 +1,20 @@
+use serde_json::{Map, Value};
+use virtual_view::EventManager;
+
+use super::{Dom, MemoryDocument, MemoryNode, Patcher};
+
+/// a `Patcher` rendering into a detached `div` of a new `MemoryDocument`
+#[inline]
+pub fn memory_patcher() -> (MemoryDocument, MemoryNode, Patcher<MemoryDocument>) {
+    let document = MemoryDocument::new();
+    let root = document.create_element("div");
+    let patcher = Patcher::new(root.clone(), document.clone(), EventManager::new());
+
+    (document, root, patcher)
+}
+
+/// the props of the json object `value`
+#[inline]
+pub fn props(value: Value) -> Map<String, Value> {
+    value.as_object().unwrap().clone()
+}
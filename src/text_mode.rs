@@ -0,0 +1,15 @@
+/// how `RawView::Text` views are rendered to the dom and to html
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TextMode {
+    /// a plain dom `Text` node
+    Node,
+    /// a `Text` node wrapped in a `span`, kept for apps that style the wrapper
+    Span,
+}
+
+impl Default for TextMode {
+    #[inline(always)]
+    fn default() -> Self {
+        TextMode::Node
+    }
+}
@@ -2,4 +2,4 @@ mod js_value_to_prop;
 mod to_html_string;
 
 pub use self::js_value_to_prop::{js_value_to_array, js_value_to_prop, js_value_to_props};
-pub use self::to_html_string::ToHtmlString;
+pub use self::to_html_string::{prop_to_html_string, ToHtmlString};
@@ -1,49 +1,86 @@
 use serde_json::{Map, Value};
 use virtual_view::{RawView, View};
 
+use super::super::{Namespace, TextMode};
+use super::super::prop_kind::props_attributes;
+use super::super::raw_html::view_raw_html;
+
 pub trait ToHtmlString {
     fn to_html_string(&self) -> String;
+
+    /// renders with `text_mode`, matching a `Patcher` using the same mode
+    #[inline]
+    fn to_html_string_with_text_mode(&self, _text_mode: TextMode) -> String {
+        self.to_html_string()
+    }
 }
 
 impl ToHtmlString for View {
     #[inline]
     fn to_html_string(&self) -> String {
+        self.to_html_string_with_text_mode(TextMode::default())
+    }
+    #[inline]
+    fn to_html_string_with_text_mode(&self, text_mode: TextMode) -> String {
         let raw_view: RawView = self.into();
-        raw_view.to_html_string()
+        raw_view.to_html_string_with_text_mode(text_mode)
     }
 }
 
 impl ToHtmlString for RawView {
     #[inline]
     fn to_html_string(&self) -> String {
-        match self {
-            &RawView::Text(ref string) => format!("<span>{}</span>", string),
-            &RawView::Data {
-                ref kind,
-                ref props,
-                ref children,
-                ..
-            } => format!(
-                "<{}{}>{}</{}>",
-                kind,
-                props_to_html_string(props),
-                children_to_html_string(children),
-                kind
-            ),
+        self.to_html_string_with_text_mode(TextMode::default())
+    }
+    #[inline]
+    fn to_html_string_with_text_mode(&self, text_mode: TextMode) -> String {
+        raw_view_to_html_string(self, Namespace::Html, text_mode)
+    }
+}
+
+#[inline]
+fn raw_view_to_html_string(view: &RawView, namespace: Namespace, text_mode: TextMode) -> String {
+    match view {
+        &RawView::Text(ref string) => if namespace.is_html() && text_mode == TextMode::Span {
+            format!("<span>{}</span>", escape_text(string))
+        } else {
+            escape_text(string)
+        },
+        &RawView::Data {
+            ref kind,
+            ref props,
+            ref children,
+            ..
+        } => {
+            let namespace = namespace.element(kind);
+
+            if let Some(html) = view_raw_html(view) {
+                format!("<{}{}>{}</{}>", kind, props_to_html_string(kind, props), html, kind)
+            } else if !namespace.is_html() && children.is_empty() {
+                format!("<{}{}/>", kind, props_to_html_string(kind, props))
+            } else {
+                format!(
+                    "<{}{}>{}</{}>",
+                    kind,
+                    props_to_html_string(kind, props),
+                    children_to_html_string(children, namespace.children(kind), text_mode),
+                    kind
+                )
+            }
         }
     }
 }
 
 #[inline]
-fn props_to_html_string(props: &Map<String, Value>) -> String {
+fn props_to_html_string(kind: &str, props: &Map<String, Value>) -> String {
     let mut out = String::new();
 
-    for (k, v) in props {
+    for (k, v) in props_attributes(Some(kind), props) {
         out.push(' ');
-        out.push_str(k);
+        out.push_str(&k);
         out.push('=');
         out.push('"');
-        out.push_str(&prop_to_html_string(v));
+        out.push_str(&escape_attribute(&v));
         out.push('"');
     }
 
@@ -51,7 +88,7 @@ fn props_to_html_string(props: &Map<String, Value>) -> String {
 }
 
 #[inline]
-fn prop_to_html_string(prop: &Value) -> String {
+pub fn prop_to_html_string(prop: &Value) -> String {
     match prop {
         &Value::Null => "null".to_string(),
         &Value::Bool(ref value) => value.to_string(),
@@ -83,16 +120,50 @@ fn prop_to_html_string(prop: &Value) -> String {
 }
 
 #[inline]
-fn children_to_html_string(children: &Vec<RawView>) -> String {
+fn children_to_html_string(
+    children: &Vec<RawView>,
+    namespace: Namespace,
+    text_mode: TextMode,
+) -> String {
     let mut out = String::new();
+    let mut prev_is_text_node = false;
 
     for child in children {
-        out.push_str(&child.to_html_string());
+        let is_text_node = match child {
+            &RawView::Text(_) => !namespace.is_html() || text_mode == TextMode::Node,
+            _ => false,
+        };
+
+        // adjacent text nodes would be merged into one by the html parser,
+        // an empty comment keeps them apart for hydration
+        if prev_is_text_node && is_text_node {
+            out.push_str("<!---->");
+        }
+        out.push_str(&raw_view_to_html_string(child, namespace, text_mode));
+
+        prev_is_text_node = is_text_node;
     }
 
     out
 }
 
+#[inline]
+fn escape_text(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+}
+
+/// escapes an attribute value for a double quoted attribute, `<` too so
+/// the html can not be read as a tag by a lax parser
+#[inline]
+fn escape_attribute(value: &str) -> String {
+    value
+        .replace('&', "&amp;")
+        .replace('"', "&quot;")
+        .replace('<', "&lt;")
+}
+
 #[test]
 fn test_to_html_string() {
     let view = view! {
@@ -102,6 +173,60 @@ fn test_to_html_string() {
     };
     assert_eq!(
         view.to_html_string(),
+        "<div array=\"0,1,2,\" class=\"Root\" style=\"color:#F00;font-size:32px;\">Hello, world!</div>"
+    );
+    assert_eq!(
+        view.to_html_string_with_text_mode(TextMode::Span),
         "<div array=\"0,1,2,\" class=\"Root\" style=\"color:#F00;font-size:32px;\"><span>Hello, world!</span></div>"
     );
 }
+
+#[test]
+fn test_to_html_string_boolean_attributes() {
+    let view = view! {
+        <div>
+            <button className="Button" disabled={false} hidden={true}/>
+            <input readOnly={true} indeterminate={true}/>
+        </div>
+    };
+    assert_eq!(
+        view.to_html_string(),
+        "<div><button class=\"Button\" hidden=\"\"></button><input readonly=\"\"></input></div>"
+    );
+}
+
+#[test]
+fn test_to_html_string_adjacent_text() {
+    let view = view! {
+        <p>{"a < b"}{"c"}</p>
+    };
+    assert_eq!(view.to_html_string(), "<p>a &lt; b<!---->c</p>");
+}
+
+#[test]
+fn test_to_html_string_attribute_escaping() {
+    let view = view! {
+        <a title={"say \"hi\" & <b>"} href="?a=1&b=2"/>
+    };
+    assert_eq!(
+        view.to_html_string(),
+        "<a href=\"?a=1&amp;b=2\" title=\"say &quot;hi&quot; &amp; &lt;b>\"></a>"
+    );
+}
+
+#[test]
+fn test_to_html_string_svg() {
+    let view = view! {
+        <div>
+            <svg viewBox="0 0 10 10">
+                <circle r=4/>
+                <text>{"label"}</text>
+                <foreignObject><p>{"html"}</p></foreignObject>
+            </svg>
+        </div>
+    };
+    assert_eq!(
+        view.to_html_string(),
+        "<div><svg viewBox=\"0 0 10 10\"><circle r=\"4\"/><text>label</text><foreignObject><p>html</p></foreignObject></svg></div>"
+    );
+}
@@ -0,0 +1,561 @@
+use std::cell::RefCell;
+use std::collections::HashMap;
+
+use serde_json::{Number, Value};
+use stdweb;
+use stdweb::{Once, Reference};
+use stdweb::unstable::TryInto;
+use stdweb::web::{Document, INode, Node};
+
+use super::{js_value_to_prop, ClientRect, Dom, DomFrameCallback, DomListener, NodeRef};
+
+thread_local! {
+    static EXCEPTION: RefCell<Option<String>> = RefCell::new(None);
+}
+
+impl Dom for Document {
+    type Node = Node;
+    type NodeKey = NodeRef;
+    type Listener = Reference;
+
+    #[inline]
+    fn node_key(node: &Node) -> NodeRef {
+        node.as_ref().into()
+    }
+
+    #[inline]
+    fn document_node(&self) -> Node {
+        self.as_node().clone()
+    }
+    #[inline]
+    fn body(&self) -> Option<Node> {
+        js! {
+            return @{self}.body;
+        }.try_into()
+            .ok()
+    }
+    #[inline]
+    fn element_by_id(&self, id: &str) -> Option<Node> {
+        js! {
+            return @{self}.getElementById(@{id});
+        }.try_into()
+            .ok()
+    }
+
+    #[inline]
+    fn node_kind(&self, node: &Node) -> Option<String> {
+        js! {
+            var node = @{node};
+            return node.nodeType === 1 ? node.tagName.toLowerCase() : null;
+        }.try_into()
+            .unwrap()
+    }
+    #[inline]
+    fn is_comment(&self, node: &Node) -> bool {
+        js! {
+            return @{node}.nodeType === 8;
+        }.try_into()
+            .unwrap()
+    }
+    #[inline]
+    fn namespace_uri(&self, node: &Node) -> Option<String> {
+        js! {
+            var node = @{node};
+            return node.nodeType === 1 ? node.namespaceURI : null;
+        }.try_into()
+            .unwrap()
+    }
+    #[inline]
+    fn text_content(&self, node: &Node) -> String {
+        node.text_content().unwrap_or_default()
+    }
+    #[inline]
+    fn attribute(&self, node: &Node, key: &str) -> Option<String> {
+        js! {
+            var node = @{node};
+            return node.nodeType === 1 ? node.getAttribute(@{key}) : null;
+        }.try_into()
+            .unwrap()
+    }
+    #[inline]
+    fn attribute_names(&self, node: &Node) -> Vec<String> {
+        js! {
+            var node = @{node},
+                names = [];
+
+            if (node.nodeType === 1) {
+                for (var i = 0, il = node.attributes.length; i < il; i++) {
+                    names.push(node.attributes[i].name);
+                }
+            }
+            return names;
+        }.try_into()
+            .unwrap()
+    }
+    #[inline]
+    fn outer_html(&self, node: &Node) -> String {
+        js! {
+            var node = @{node};
+            return node.nodeType === 1 ? node.outerHTML : node.textContent;
+        }.try_into()
+            .unwrap()
+    }
+
+    #[inline]
+    fn create_element(&self, kind: &str) -> Node {
+        match self.create_element(kind) {
+            Ok(element) => element.into(),
+            Err(error) => {
+                set_exception(error.to_string());
+                self.create_text_node("").into()
+            }
+        }
+    }
+    #[inline]
+    fn create_element_ns(&self, namespace: &str, kind: &str) -> Node {
+        let result = js! {
+            try {
+                return @{self}.createElementNS(@{namespace}, @{kind});
+            } catch (e) {
+                return String(e);
+            }
+        };
+
+        match result {
+            stdweb::Value::String(message) => {
+                set_exception(message);
+                self.create_text_node("").into()
+            }
+            result => result.try_into().unwrap(),
+        }
+    }
+    #[inline]
+    fn create_text_node(&self, text: &str) -> Node {
+        self.create_text_node(text).into()
+    }
+    #[inline]
+    fn attach_shadow(&self, host: &Node) -> Result<Node, String> {
+        let result = js! {
+            try {
+                var host = @{host};
+                return host.shadowRoot || host.attachShadow({ mode: "open" });
+            } catch (e) {
+                return String(e);
+            }
+        };
+
+        match result {
+            stdweb::Value::String(message) => Err(message),
+            result => result
+                .try_into()
+                .map_err(|_| "attachShadow did not return a shadow root".to_string()),
+        }
+    }
+    #[inline]
+    fn set_inner_html(&self, node: &Node, html: &str) {
+        catch_exception(js! {
+            try {
+                var node = @{node};
+                node.innerHTML = @{html};
+            } catch (e) {
+                return String(e);
+            }
+        });
+    }
+    #[inline]
+    fn set_text_content(&self, node: &Node, text: &str) {
+        node.set_text_content(text);
+    }
+
+    #[inline]
+    fn parent_node(&self, node: &Node) -> Option<Node> {
+        node.parent_node()
+    }
+    #[inline]
+    fn child_nodes(&self, node: &Node) -> Vec<Node> {
+        node.child_nodes().iter().collect()
+    }
+    #[inline]
+    fn append_child(&self, parent: &Node, child: &Node) {
+        parent.append_child(child);
+    }
+    #[inline]
+    fn insert_before(&self, parent: &Node, child: &Node, reference: &Node) {
+        if let Err(error) = parent.insert_before(child, reference) {
+            set_exception(error.to_string());
+        }
+    }
+    #[inline]
+    fn remove_child(&self, parent: &Node, child: &Node) {
+        if let Err(error) = parent.remove_child(child) {
+            set_exception(error.to_string());
+        }
+    }
+    #[inline]
+    fn replace_child(&self, parent: &Node, new_child: &Node, old_child: &Node) {
+        if let Err(error) = parent.replace_child(new_child, old_child) {
+            set_exception(error.to_string());
+        }
+    }
+
+    #[inline]
+    fn set_attribute(&self, node: &Node, key: &str, value: &str) {
+        catch_exception(js! {
+            try {
+                var node = @{node};
+                node.setAttribute(@{key}, @{value});
+            } catch (e) {
+                return String(e);
+            }
+        });
+    }
+    #[inline]
+    fn remove_attribute(&self, node: &Node, key: &str) {
+        catch_exception(js! {
+            try {
+                var node = @{node};
+                node.removeAttribute(@{key});
+            } catch (e) {
+                return String(e);
+            }
+        });
+    }
+    #[inline]
+    fn set_attribute_ns(&self, node: &Node, namespace: &str, key: &str, value: &str) {
+        catch_exception(js! {
+            try {
+                var node = @{node};
+                node.setAttributeNS(@{namespace}, @{key}, @{value});
+            } catch (e) {
+                return String(e);
+            }
+        });
+    }
+    #[inline]
+    fn remove_attribute_ns(&self, node: &Node, namespace: &str, local_name: &str) {
+        catch_exception(js! {
+            try {
+                var node = @{node};
+                node.removeAttributeNS(@{namespace}, @{local_name});
+            } catch (e) {
+                return String(e);
+            }
+        });
+    }
+    #[inline]
+    fn property(&self, node: &Node, key: &str) -> Value {
+        let value = js! {
+            try {
+                return @{node}[@{key}];
+            } catch (e) {
+                return null;
+            }
+        };
+
+        match value {
+            stdweb::Value::Bool(v) => Value::Bool(v),
+            stdweb::Value::String(v) => Value::String(v),
+            stdweb::Value::Number(v) => {
+                let number: Result<f64, _> = v.try_into();
+                number
+                    .ok()
+                    .and_then(Number::from_f64)
+                    .map_or(Value::Null, Value::Number)
+            }
+            _ => Value::Null,
+        }
+    }
+    #[inline]
+    fn set_property(&self, node: &Node, key: &str, value: &Value) {
+        let value = match json_to_js_value(value) {
+            Ok(value) => value,
+            Err(error) => return set_exception(error),
+        };
+        catch_exception(js! {
+            try {
+                var node = @{node},
+                    key = @{key},
+                    value = @{value};
+
+                node[key] = value;
+            } catch (e) {
+                return String(e);
+            }
+        });
+    }
+    #[inline]
+    fn set_value(&self, node: &Node, value: &str) {
+        if Dom::property(self, node, "value").as_str() == Some(value) {
+            return;
+        }
+        let selection = focused_selection(node);
+
+        Dom::set_property(self, node, "value", &Value::String(value.into()));
+
+        if let Some((start, end)) = selection {
+            // js lengths count utf-16 code units
+            let length = value.encode_utf16().count() as u32;
+            js! {
+                try {
+                    @{node}.setSelectionRange(@{start.min(length)}, @{end.min(length)});
+                } catch (e) {}
+            };
+        }
+    }
+    #[inline]
+    fn set_style(&self, node: &Node, name: &str, value: &str, important: bool) {
+        catch_exception(js! {
+            try {
+                var priority = @{important} ? "important" : "";
+                @{node}.style.setProperty(@{name}, @{value}, priority);
+            } catch (e) {
+                return String(e);
+            }
+        });
+    }
+    #[inline]
+    fn remove_style(&self, node: &Node, name: &str) {
+        catch_exception(js! {
+            try {
+                @{node}.style.removeProperty(@{name});
+            } catch (e) {
+                return String(e);
+            }
+        });
+    }
+    #[inline]
+    fn add_class(&self, node: &Node, name: &str) {
+        catch_exception(js! {
+            try {
+                @{node}.classList.add(@{name});
+            } catch (e) {
+                return String(e);
+            }
+        });
+    }
+    #[inline]
+    fn remove_class(&self, node: &Node, name: &str) {
+        catch_exception(js! {
+            try {
+                @{node}.classList.remove(@{name});
+            } catch (e) {
+                return String(e);
+            }
+        });
+    }
+
+    #[inline]
+    fn focus(&self, node: &Node) {
+        call_method(node, "focus");
+    }
+    #[inline]
+    fn blur(&self, node: &Node) {
+        call_method(node, "blur");
+    }
+    #[inline]
+    fn select(&self, node: &Node) {
+        call_method(node, "select");
+    }
+    #[inline]
+    fn scroll_into_view(&self, node: &Node) {
+        call_method(node, "scrollIntoView");
+    }
+    #[inline]
+    fn click(&self, node: &Node) {
+        call_method(node, "click");
+    }
+    #[inline]
+    fn bounding_client_rect(&self, node: &Node) -> ClientRect {
+        let rect = js! {
+            try {
+                var rect = @{node}.getBoundingClientRect();
+                return [rect.left, rect.top, rect.width, rect.height];
+            } catch (e) {
+                return [0, 0, 0, 0];
+            }
+        };
+        let rect: Vec<f64> = rect.try_into().unwrap_or_else(|_| vec![0.0; 4]);
+
+        ClientRect {
+            left: rect[0],
+            top: rect[1],
+            width: rect[2],
+            height: rect[3],
+        }
+    }
+
+    #[inline]
+    fn add_event_listener(
+        &self,
+        target: &Node,
+        name: &str,
+        listener: DomListener<Node>,
+    ) -> Reference {
+        let listener = move |event: Reference| {
+            listener(event_path(&event), js_value_to_prop(event.try_into().unwrap()));
+        };
+
+        js! {
+            var type = @{name},
+                target = @{target},
+                listener = @{listener};
+
+            target.addEventListener(type, listener);
+            return listener;
+        }.try_into()
+            .unwrap()
+    }
+    #[inline]
+    fn remove_event_listener(&self, target: &Node, name: &str, listener: &Reference) {
+        js! {
+            var type = @{name},
+                target = @{target},
+                listener = @{listener};
+
+            target.removeEventListener(type, listener);
+        };
+    }
+
+    #[inline]
+    fn request_animation_frame(&self, mut callback: DomFrameCallback) {
+        let callback = move |_: f64| callback();
+
+        js! {
+            var callback = @{Once(callback)};
+            requestAnimationFrame(callback);
+        };
+    }
+    #[inline]
+    fn now(&self) -> f64 {
+        js! {
+            return performance.now();
+        }.try_into()
+            .unwrap()
+    }
+
+    #[inline]
+    fn take_exception(&self) -> Option<String> {
+        EXCEPTION.with(|exception| exception.borrow_mut().take())
+    }
+
+    #[inline]
+    fn warn(&self, message: &str) {
+        js! {
+            console.warn(@{message});
+        };
+    }
+}
+
+#[inline]
+fn set_exception(message: String) {
+    EXCEPTION.with(|exception| *exception.borrow_mut() = Some(message));
+}
+
+/// the nodes `event` went through, innermost first, across shadow roots when
+/// the browser has `composedPath`, from its target up otherwise
+#[inline]
+fn event_path(event: &Reference) -> Vec<Node> {
+    let composed_path: Option<stdweb::Array> = js! {
+        var event = @{event};
+        return event.composedPath ? event.composedPath() : null;
+    }.try_into()
+        .unwrap_or(None);
+
+    if let Some(composed_path) = composed_path {
+        return Vec::<stdweb::Value>::from(composed_path)
+            .into_iter()
+            .filter_map(|node| node.try_into().ok())
+            .collect();
+    }
+
+    let mut path = Vec::new();
+    let mut node: Option<Node> = js! {
+        return @{event}.target;
+    }.try_into()
+        .ok();
+
+    while let Some(current) = node {
+        node = current.parent_node();
+        path.push(current);
+    }
+    path
+}
+
+/// the selection of `node` if it is focused and has one, so it can be kept
+/// when its value is set
+#[inline]
+fn focused_selection(node: &Node) -> Option<(u32, u32)> {
+    let focused: bool = js! {
+        var node = @{node},
+            root = node.getRootNode ? node.getRootNode() : node.ownerDocument;
+        return root.activeElement === node;
+    }.try_into()
+        .unwrap();
+
+    if !focused {
+        return None;
+    }
+    let selection: Option<Vec<u32>> = js! {
+        try {
+            var node = @{node};
+            return typeof node.selectionStart === "number"
+                ? [node.selectionStart, node.selectionEnd]
+                : null;
+        } catch (e) {
+            return null;
+        }
+    }.try_into()
+        .unwrap_or(None);
+
+    match selection {
+        Some(ref selection) if selection.len() == 2 => Some((selection[0], selection[1])),
+        _ => None,
+    }
+}
+
+/// calls the method `name` of `node` without arguments
+#[inline]
+fn call_method(node: &Node, name: &str) {
+    catch_exception(js! {
+        try {
+            @{node}[@{name}]();
+        } catch (e) {
+            return String(e);
+        }
+    });
+}
+
+/// records the message a `js!` block returns from its `catch`
+#[inline]
+fn catch_exception(result: stdweb::Value) {
+    if let stdweb::Value::String(message) = result {
+        set_exception(message);
+    }
+}
+
+#[inline]
+fn json_to_js_value(value: &Value) -> Result<stdweb::Value, String> {
+    match value {
+        &Value::Null => Ok(stdweb::Value::Null),
+        &Value::Bool(ref v) => Ok(stdweb::Value::Bool(*v)),
+        &Value::Number(ref v) => match v.as_f64() {
+            Some(number) => Ok(stdweb::Value::from(number)),
+            None => Err(format!("{} can not be converted to a js number", v)),
+        },
+        &Value::String(ref v) => Ok(stdweb::Value::String(v.clone())),
+        &Value::Array(ref a) => {
+            let array = a.iter()
+                .map(json_to_js_value)
+                .collect::<Result<Vec<_>, _>>()?;
+            Ok(array.try_into().unwrap())
+        }
+        &Value::Object(ref o) => {
+            let mut object = HashMap::new();
+
+            for (k, v) in o {
+                object.insert(k.clone(), json_to_js_value(v)?);
+            }
+            Ok(object.try_into().unwrap())
+        }
+    }
+}